@@ -10,6 +10,9 @@ pub struct Animation {
     current_key_frame_time: instant::Duration,
 
     animations_speed: f32,
+
+    is_looping: bool,
+    is_finished: bool, // the last key frame of an animation which does not loop is reached
 }
 
 impl Animation {
@@ -18,6 +21,8 @@ impl Animation {
             max_key_frame_time: 0.0,
             current_key_frame_time: instant::Duration::default(),
             animations_speed: 1.0,
+            is_looping: true,
+            is_finished: false,
         }
     }
 
@@ -38,6 +43,8 @@ impl Animation {
 
             current_key_frame_time: instant::Duration::ZERO,
             animations_speed: 1.0,
+            is_looping: true,
+            is_finished: false,
         }
     }
 
@@ -50,11 +57,31 @@ impl Animation {
         self.animations_speed = speed;
     }
 
+    pub fn set_looping(&mut self, is_looping: bool) {
+        self.is_looping = is_looping;
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.is_finished
+    }
+
     pub fn increment_time(&mut self, dt: &instant::Duration) {
+        if self.is_finished {
+            return;
+        }
+
         self.current_key_frame_time += *dt;
 
         if self.get_current_key_frame_time() > self.max_key_frame_time {
-            self.current_key_frame_time = instant::Duration::ZERO;
+            if self.is_looping {
+                self.current_key_frame_time = instant::Duration::ZERO;
+            } else {
+                // hold the last key frame
+                self.current_key_frame_time = instant::Duration::from_secs_f32(
+                    self.max_key_frame_time / self.animations_speed,
+                );
+                self.is_finished = true;
+            }
         }
     }
 
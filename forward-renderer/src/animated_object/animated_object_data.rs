@@ -44,7 +44,7 @@ impl AnimationRotation {
 
 #[derive(Clone)]
 pub struct AnimationData {
-    pub _name: String,
+    pub name: String,
     pub _joint_target_names: Vec<String>,
     pub joint_translations: Vec<AnimationTranslation>,
     pub joint_rotations: Vec<AnimationRotation>,
@@ -52,7 +52,7 @@ pub struct AnimationData {
 
 impl std::fmt::Debug for AnimationData {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        writeln!(f, "Name: {}", self._name)?;
+        writeln!(f, "Name: {}", self.name)?;
         write!(f, "Joints: ")?;
         for name in &self._joint_target_names {
             write!(f, "{} ", name)?;
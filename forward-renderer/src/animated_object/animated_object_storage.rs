@@ -10,6 +10,13 @@ use crate::animated_object::animated_object_data::AnimationData;
 use crate::animated_object::gltf_importer::GltfImporter;
 use crate::animation_shader::{self, AnimationShaderDraw};
 
+/// An instance playing its death animation once, fading out afterwards and freed at the end
+struct Despawn {
+    is_animated: bool, // without a death animation the fade starts at once
+    fade_time: instant::Duration,
+    fade_duration: instant::Duration,
+}
+
 struct AnimationObjectInstance {
     current_animation_index: usize,
     current_animation: Animation,
@@ -20,6 +27,7 @@ struct AnimationObjectInstance {
     _requires_update: bool,
 
     is_active: bool,
+    despawn: Option<Despawn>,
 }
 
 struct AnimationObjectInstanceDevice {
@@ -34,6 +42,7 @@ pub struct AnimatedObjectStorage {
 
     // host instance data
     instance_data: Vec<AnimationObjectInstance>,
+    free_instances: Vec<usize>, // inactive instances, the next one to be reused last
 
     // device data
     mesh: animation_shader::Mesh,
@@ -45,12 +54,13 @@ pub struct AnimatedObjectStorage {
 }
 
 impl AnimatedObjectStorage {
+    /// The instances start with the first animation, the model needs at least one
     pub fn create_from_glb(
         wgpu_renderer: &mut dyn wgpu_renderer::wgpu_renderer::WgpuRendererInterface,
         animation_bind_group_layout: &animation_shader::AnimationBindGroupLayout,
         glb_bin: &[u8],
         max_instances: usize,
-    ) -> Self {
+    ) -> Result<Self, String> {
         // imported data
         let animation_object_data = GltfImporter::create(glb_bin);
         let mesh_data = animation_object_data.mesh;
@@ -60,13 +70,16 @@ impl AnimatedObjectStorage {
         // host data
         let skeleton = Skeleton::new(&skeleton_data);
         let animations = animations_data;
+        if animations.is_empty() {
+            return Err("the model has no animations".to_string());
+        }
         // println!("skeleton {:?}", skeleton);
         // println!("animations {:?}", animations);
 
         // host instance data
         let mut instance_data: Vec<AnimationObjectInstance> = Vec::new();
         for _i in 0..max_instances {
-            let current_animation_index = 1;
+            let current_animation_index = 0;
             let current_animation = Animation::new(&animations[current_animation_index]);
 
             let position = cgmath::Vector3::new(0.0, 20.0, 5.0);
@@ -86,8 +99,10 @@ impl AnimatedObjectStorage {
                 transformations,
                 _requires_update: requires_update,
                 is_active,
+                despawn: None,
             });
         }
+        let free_instances = (0..max_instances).rev().collect();
 
         // device data
         let mesh = animation_shader::Mesh::from_animation_data(wgpu_renderer, &mesh_data);
@@ -110,38 +125,59 @@ impl AnimatedObjectStorage {
             });
         }
 
-        Self {
+        Ok(Self {
             skeleton,
             animations,
             instance_data,
+            free_instances,
             mesh,
             instance_data_device,
             max_instances,
-        }
+        })
     }
 
-    /// Updates the animations
+    /// Updates the animations and fades out the despawned instances
     pub fn update_animations(&mut self, dt: &instant::Duration) {
-        for elem in &mut self.instance_data {
-            if elem.is_active {
-                // update time
-                elem.current_animation.increment_time(dt);
-
-                // calculate transformations
-                elem.current_animation.update_animation_uniform(
-                    &self.skeleton,
-                    &self.animations[elem.current_animation_index],
-                    &mut elem.transformations,
-                );
+        for (id, elem) in self.instance_data.iter_mut().enumerate() {
+            if !elem.is_active {
+                continue;
+            }
+
+            // update time
+            elem.current_animation.increment_time(dt);
+
+            // calculate transformations
+            elem.current_animation.update_animation_uniform(
+                &self.skeleton,
+                &self.animations[elem.current_animation_index],
+                &mut elem.transformations,
+            );
+
+            // the fade starts once the death animation has been played
+            if let Some(despawn) = &mut elem.despawn
+                && (!despawn.is_animated || elem.current_animation.is_finished())
+            {
+                despawn.fade_time += *dt;
+                let fade = despawn.fade_time.as_secs_f32() / despawn.fade_duration.as_secs_f32();
+                elem.instance.color[3] = (1.0 - fade).max(0.0);
+
+                if despawn.fade_time >= despawn.fade_duration {
+                    elem.is_active = false;
+                    elem.despawn = None;
+                    self.free_instances.push(id);
+                }
             }
         }
     }
 
-    /// Copies the data from the host to the device
+    /// Copies the data of the active instances from the host to the device
     pub fn update_device_data(&mut self, renderer: &mut dyn WgpuRendererInterface) {
         for i in 0..self.max_instances {
             // host data
             let instance_data_host = &self.instance_data[i];
+            if !instance_data_host.is_active {
+                continue;
+            }
             let instance_host = instance_data_host.instance;
             let transformations_host = &instance_data_host.transformations;
 
@@ -224,8 +260,93 @@ impl AnimatedObjectStorage {
         self.instance_data[id].instance.model = model.into();
     }
 
+    pub fn set_color(&mut self, id: usize, color: [f32; 4]) {
+        self.instance_data[id].instance.color = color;
+    }
+
+    /// Activates a free instance, without one the instance furthest into its fade out is reused
+    pub fn spawn(&mut self) -> Option<usize> {
+        let id = match self.free_instances.pop() {
+            Some(id) => id,
+            None => self
+                .instance_data
+                .iter()
+                .enumerate()
+                .filter_map(|(id, elem)| elem.despawn.as_ref().map(|despawn| (id, despawn)))
+                .max_by_key(|(_, despawn)| despawn.fade_time)
+                .map(|(id, _)| id)?,
+        };
+
+        self.set_active(id);
+        Some(id)
+    }
+
+    /// Plays the animation once, fades the instance out and frees it afterwards
+    ///
+    /// Without an animation the current one keeps playing while the instance fades out.
+    pub fn despawn(
+        &mut self,
+        id: usize,
+        animation: Option<usize>,
+        fade_duration: instant::Duration,
+    ) {
+        if !self.instance_data[id].is_active {
+            return;
+        }
+
+        if let Some(animation) = animation {
+            self.set_animation(id, animation);
+            self.instance_data[id].current_animation.set_looping(false);
+        }
+        self.instance_data[id].despawn = Some(Despawn {
+            is_animated: animation.is_some(),
+            fade_time: instant::Duration::ZERO,
+            fade_duration,
+        });
+    }
+
     pub fn set_active(&mut self, id: usize) {
-        self.instance_data[id].is_active = true;
+        self.free_instances.retain(|free_id| *free_id != id);
+
+        // a reused instance starts its animation over, looping again
+        let elem = &mut self.instance_data[id];
+        elem.is_active = true;
+        elem.despawn = None;
+        elem.instance.color[3] = 1.0;
+        elem.current_animation = Animation::new(&self.animations[elem.current_animation_index]);
+    }
+
+    /// Hides the instance at once and frees it
+    pub fn set_inactive(&mut self, id: usize) {
+        let elem = &mut self.instance_data[id];
+        if elem.is_active {
+            elem.is_active = false;
+            elem.despawn = None;
+            self.free_instances.push(id);
+        }
+    }
+
+    pub fn is_active(&self, id: usize) -> bool {
+        self.instance_data[id].is_active
+    }
+
+    /// Index of the animation with the given glTF name, the error lists the available names
+    pub fn animation_index(&self, name: &str) -> Result<usize, String> {
+        self.animations
+            .iter()
+            .position(|elem| elem.name == name)
+            .ok_or_else(|| {
+                let names: Vec<&str> = self
+                    .animations
+                    .iter()
+                    .map(|elem| elem.name.as_str())
+                    .collect();
+                format!(
+                    "no animation '{}', expected one of: {}",
+                    name,
+                    names.join(", ")
+                )
+            })
     }
 
     pub fn set_animation(&mut self, id: usize, animation: usize) {
@@ -255,9 +376,14 @@ impl std::fmt::Debug for AnimatedObjectStorage {
     }
 }
 
-impl AnimationShaderDraw for AnimatedObjectStorage {
-    fn draw<'a>(&'a self, render_pass: &mut wgpu::RenderPass<'a>) {
+impl AnimatedObjectStorage {
+    fn draw_instances<'a>(&'a self, render_pass: &mut wgpu::RenderPass<'a>, is_fading: bool) {
         for i in 0..self.max_instances {
+            let elem = &self.instance_data[i];
+            if !elem.is_active || (elem.instance.color[3] < 1.0) != is_fading {
+                continue;
+            }
+
             let mesh = &self.mesh;
             let instance_data_device = &self.instance_data_device[i];
 
@@ -269,3 +395,13 @@ impl AnimationShaderDraw for AnimatedObjectStorage {
         }
     }
 }
+
+impl AnimationShaderDraw for AnimatedObjectStorage {
+    fn draw<'a>(&'a self, render_pass: &mut wgpu::RenderPass<'a>) {
+        self.draw_instances(render_pass, false);
+    }
+
+    fn draw_fading<'a>(&'a self, render_pass: &mut wgpu::RenderPass<'a>) {
+        self.draw_instances(render_pass, true);
+    }
+}
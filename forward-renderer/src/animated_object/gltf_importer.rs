@@ -242,7 +242,7 @@ impl GltfImporter {
             }
 
             let animation_data_element = AnimationData {
-                _name: animation_name.to_string(),
+                name: animation_name.to_string(),
                 _joint_target_names: joint_target_names,
                 joint_translations,
                 joint_rotations,
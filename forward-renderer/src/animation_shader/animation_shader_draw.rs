@@ -3,4 +3,7 @@
 
 pub trait AnimationShaderDraw {
     fn draw<'a>(&'a self, render_pass: &mut wgpu::RenderPass<'a>);
+
+    /// Draws the see-through instances, after all the opaque objects
+    fn draw_fading<'a>(&'a self, render_pass: &mut wgpu::RenderPass<'a>);
 }
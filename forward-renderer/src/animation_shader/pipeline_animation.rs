@@ -19,6 +19,7 @@ pub enum LightingModel {
 /// A general purpose shader using vertices, colors and an instance matrix
 pub struct Pipeline {
     render_pipeline: wgpu::RenderPipeline,
+    is_fading: bool,
 }
 
 impl Pipeline {
@@ -36,6 +37,7 @@ impl Pipeline {
             surface_format,
             wgpu::PrimitiveTopology::LineList,
             lighting,
+            false,
         )
     }
 
@@ -53,6 +55,26 @@ impl Pipeline {
             surface_format,
             wgpu::PrimitiveTopology::TriangleList,
             lighting,
+            false,
+        )
+    }
+
+    /// Blends the fading instances, they do not hide what is drawn behind them afterwards
+    pub fn new_fading(
+        device: &wgpu::Device,
+        camera_bind_group_layout: &CameraBindGroupLayout,
+        animation_bind_group_layout: &AnimationBindGroupLayout,
+        surface_format: wgpu::TextureFormat,
+        lighting: &LightingModel,
+    ) -> Self {
+        Self::new_parameterized(
+            device,
+            camera_bind_group_layout,
+            animation_bind_group_layout,
+            surface_format,
+            wgpu::PrimitiveTopology::TriangleList,
+            lighting,
+            true,
         )
     }
 
@@ -63,6 +85,7 @@ impl Pipeline {
         surface_format: wgpu::TextureFormat,
         topology: wgpu::PrimitiveTopology,
         lighting: &LightingModel,
+        is_fading: bool,
     ) -> Self {
         // Shader
         let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
@@ -98,7 +121,7 @@ impl Pipeline {
                 entry_point: Some("fs_main"),
                 targets: &[Some(wgpu::ColorTargetState {
                     format: surface_format,
-                    blend: None,
+                    blend: is_fading.then_some(wgpu::BlendState::ALPHA_BLENDING),
                     write_mask: wgpu::ColorWrites::ALL,
                 })],
                 compilation_options: wgpu::PipelineCompilationOptions::default(),
@@ -118,7 +141,7 @@ impl Pipeline {
             },
             depth_stencil: Some(wgpu::DepthStencilState {
                 format: depth_texture::DepthTexture::DEPTH_FORMAT,
-                depth_write_enabled: Some(true),
+                depth_write_enabled: Some(!is_fading),
                 depth_compare: Some(wgpu::CompareFunction::Less),
                 stencil: wgpu::StencilState::default(),
                 bias: wgpu::DepthBiasState::default(),
@@ -132,7 +155,10 @@ impl Pipeline {
             multiview_mask: None,
         });
 
-        Self { render_pipeline }
+        Self {
+            render_pipeline,
+            is_fading,
+        }
     }
 
     pub fn draw<'a>(
@@ -143,6 +169,10 @@ impl Pipeline {
     ) {
         render_pass.set_pipeline(&self.render_pipeline);
         camera.bind(render_pass);
-        mesh.draw(render_pass);
+        if self.is_fading {
+            mesh.draw_fading(render_pass);
+        } else {
+            mesh.draw(render_pass);
+        }
     }
 }
@@ -38,7 +38,7 @@ struct InstanceInput {
 
 struct VertexOutput {
     @builtin(position) clip_position: vec4<f32>,
-    @location(0) color: vec3<f32>,
+    @location(0) color: vec4<f32>, // the alpha fades out despawned objects
     @location(1) position: vec3<f32>,
     @location(2) normal: vec3<f32>,
 };
@@ -58,7 +58,7 @@ fn vs_main(
     // calculate output
     var out: VertexOutput;
     out.clip_position = camera.view_proj * vec4<f32>(position, 1.0);
-    out.color = color.xyz;
+    out.color = color;
     out.position = position;
     out.normal = normal;
 
@@ -103,7 +103,7 @@ fn vs_main_gouraud(
 
     var out: VertexOutput;
     out.clip_position = camera.view_proj * vec4<f32>(position, 1.0);
-    out.color = out_color;
+    out.color = vec4<f32>(out_color, color.w);
     out.position = position;
     out.normal = normal;
 
@@ -119,7 +119,7 @@ struct FragmentOutput {
 @fragment
 fn fs_main(in: VertexOutput) -> FragmentOutput {
 
-    let color_out = vec4<f32>(in.color, 1.0);
+    let color_out = in.color;
 
     var out: FragmentOutput;
     out.surface = color_out;
@@ -62,6 +62,7 @@ pub struct ForwardRenderer {
     // pipeline_deferred_light_sphere: deferred_light_sphere_shader::Pipeline,
     pub animation_bind_group_layout: animation_shader::AnimationBindGroupLayout,
     pipeline_animated: animation_shader::Pipeline,
+    pipeline_animated_fading: animation_shader::Pipeline,
 
     pub heightmap_bind_group_layout: lod_heightmap_shader::HeightmapBindGroupLayout,
     pipeline_lod_heightmap: lod_heightmap_shader::Pipeline,
@@ -204,6 +205,13 @@ impl ForwardRenderer {
             surface_format,
             &settings.animation_lighting,
         );
+        let pipeline_animated_fading = animation_shader::Pipeline::new_fading(
+            wgpu_renderer.device(),
+            &camera_bind_group_layout,
+            &animation_bind_group_layout,
+            surface_format,
+            &settings.animation_lighting,
+        );
 
         // pipeline deferred heightmap
         let heightmap_bind_group_layout =
@@ -330,6 +338,7 @@ impl ForwardRenderer {
             // pipeline_deferred_light_sphere,
             animation_bind_group_layout,
             pipeline_animated,
+            pipeline_animated_fading,
 
             heightmap_bind_group_layout,
             pipeline_lod_heightmap,
@@ -641,6 +650,15 @@ impl ForwardRenderer {
                 .draw_lines(&mut render_pass, &self.camera_uniform_buffer, *elem);
         }
 
+        // fading animations, behind the opaque objects
+        for elem in animations {
+            self.pipeline_animated_fading.draw(
+                &mut render_pass,
+                &self.camera_uniform_buffer,
+                *elem,
+            );
+        }
+
         // particle shader
 
         for elem in plasmas {
@@ -0,0 +1,318 @@
+//! Abilities cast by the units once their mana is full, defined in res/units.json
+//!
+//! Mana fills while a unit deals and takes damage, a cast uses it all up. The controller plays the
+//! cast and the worker resolves its effect on the other units.
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    status_effect::StatusEffectDefinition,
+    unit_definition::{self, UnitDefinitions},
+};
+
+type Vec2 = cgmath::Vector2<f32>;
+
+/// Mana gained per point of damage
+pub const MANA_PER_DAMAGE_DEALT: f32 = 1.0;
+pub const MANA_PER_DAMAGE_TAKEN: f32 = 0.5;
+
+/// Distance of the summoned units to their summoner
+pub const SUMMON_DISTANCE: f32 = 2.0;
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case", deny_unknown_fields)]
+pub enum AbilityKind {
+    Slam {
+        radius: f32,
+        damage: f32,
+        #[serde(default)]
+        effect: Option<StatusEffectDefinition>, // applied to every enemy hit
+    },
+    HealPulse {
+        radius: f32,
+        heal: f32,
+    },
+    Dash {
+        distance: f32,
+    },
+    Summon {
+        unit_type: String,
+        count: usize,
+    },
+}
+
+impl AbilityKind {
+    pub fn name(&self) -> &'static str {
+        match self {
+            AbilityKind::Slam { .. } => "slam",
+            AbilityKind::HealPulse { .. } => "heal_pulse",
+            AbilityKind::Dash { .. } => "dash",
+            AbilityKind::Summon { .. } => "summon",
+        }
+    }
+}
+
+/// An ability as given in the unit definitions, in seconds
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct AbilityDefinition {
+    pub kind: AbilityKind,
+    pub cooldown: f32,
+    pub cast_time: f32,
+    pub mana_cost: f32,
+    #[serde(default)]
+    pub animation: Option<String>, // glTF animation played while casting, None keeps the current one
+}
+
+impl AbilityDefinition {
+    pub fn validate(&self, unit_definitions: &UnitDefinitions) -> Result<(), String> {
+        let mut values = vec![
+            ("ability.cooldown", self.cooldown),
+            ("ability.cast_time", self.cast_time),
+            ("ability.mana_cost", self.mana_cost),
+        ];
+        match &self.kind {
+            AbilityKind::Slam {
+                radius,
+                damage,
+                effect,
+            } => {
+                values.push(("ability.kind.slam.radius", *radius));
+                values.push(("ability.kind.slam.damage", *damage));
+                if let Some(effect) = effect {
+                    effect
+                        .validate()
+                        .map_err(|err| format!("ability.kind.slam.{}", err))?;
+                }
+            }
+            AbilityKind::HealPulse { radius, heal } => {
+                values.push(("ability.kind.heal_pulse.radius", *radius));
+                values.push(("ability.kind.heal_pulse.heal", *heal));
+            }
+            AbilityKind::Dash { distance } => {
+                values.push(("ability.kind.dash.distance", *distance));
+            }
+            AbilityKind::Summon { unit_type, count } => {
+                unit_definitions
+                    .index_of(unit_type)
+                    .map_err(|err| format!("ability.kind.summon: {}", err))?;
+                if *count == 0 {
+                    return Err("ability.kind.summon.count must be positive".to_string());
+                }
+            }
+        }
+
+        for (field, value) in values {
+            if !value.is_finite() || value <= 0.0 {
+                return Err(format!("{} must be positive, found {}", field, value));
+            }
+        }
+        if self
+            .animation
+            .as_ref()
+            .is_some_and(|animation| animation.is_empty())
+        {
+            return Err("ability.animation is empty".to_string());
+        }
+
+        Ok(())
+    }
+}
+
+/// An ability of a unit, with its durations in ticks
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Ability {
+    pub kind: AbilityKind,
+    pub cooldown: u64,
+    pub cast_duration: u64,
+    pub mana_cost: f32,
+}
+
+impl Ability {
+    pub fn new(definition: &AbilityDefinition) -> Self {
+        Self {
+            kind: definition.kind.clone(),
+            cooldown: unit_definition::seconds_to_ticks(definition.cooldown),
+            cast_duration: unit_definition::seconds_to_ticks(definition.cast_time),
+            mana_cost: definition.mana_cost,
+        }
+    }
+}
+
+/// A finished cast, waiting to be resolved by the worker
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Cast {
+    pub owner_id: usize,
+    pub position: Vec2,
+    pub kind: AbilityKind,
+}
+
+#[test]
+fn test_abilities_are_cast_with_full_mana() {
+    use std::sync::mpsc;
+
+    use crate::{
+        ant_ai::AntBodyInterface,
+        ant_controller::{AntAction, AntAnimation},
+        battle_event::BattleEventKind,
+        game_board::Faction,
+        scenario::{Scenario, UnitSpawn},
+        worker::{Worker, WorkerMessage},
+    };
+
+    let unit_definitions = UnitDefinitions::load_default().unwrap();
+
+    // every caster with what its ability works on
+    let mut units = Vec::new();
+    for (id, unit_type, faction, x, y) in [
+        (0, "heavy_ant", Faction::Blue, 0.0, 0.0),
+        (1, "heavy_ant", Faction::Red, 2.0, 0.0), // in reach of the slam
+        (2, "medic_ant", Faction::Blue, 0.0, 20.0),
+        (3, "ant", Faction::Blue, 0.0, 22.0), // hurt ally of the medic
+        (4, "leaper_ant", Faction::Blue, -30.0, 0.0),
+        (5, "brood_ant", Faction::Blue, -10.0, 20.0),
+    ] {
+        units.push(UnitSpawn {
+            id,
+            unit_type: unit_type.to_string(),
+            faction,
+            position: cgmath::Vector2::new(x, y),
+        });
+    }
+    let scenario = Scenario { units };
+
+    let (_channel_0_tx, channel_0_rx) = mpsc::channel();
+    let (channel_1_tx, channel_1_rx) = mpsc::channel();
+    let mut worker = Worker::new(
+        channel_0_rx,
+        channel_1_tx,
+        scenario,
+        unit_definitions.clone(),
+        16,
+        16,
+    );
+    worker.start_battle(0).unwrap();
+
+    // the ants are reached through a save game, the player army comes first
+    let index_of = |ants: &[crate::ant_unit::AntUnit], id| ants.iter().position(|ant| ant.id == id);
+
+    // fill the mana of the casters, the leaper by the damage it takes
+    let mut save_game = worker.save(0);
+    let start_tick = save_game.tick;
+    let ants = &mut save_game.ants;
+    for id in [0, 2, 5] {
+        let index = index_of(ants, id).unwrap();
+        let controller = &mut ants[index].controller;
+        assert!(!controller.is_ability_ready());
+        controller.gain_mana(1000.0);
+        assert!(controller.is_ability_ready());
+    }
+    let mana_cost = unit_definitions
+        .get("leaper_ant")
+        .unwrap()
+        .ability
+        .as_ref()
+        .unwrap()
+        .mana_cost;
+    let index = index_of(ants, 4).unwrap();
+    let leaper = &mut ants[index].controller;
+    leaper.apply_damage(mana_cost / MANA_PER_DAMAGE_TAKEN, &mut Vec::new());
+    assert!(leaper.is_ability_ready());
+    let leaper_position = leaper.get_position();
+    let index = index_of(ants, 3).unwrap();
+    ants[index].controller.apply_damage(50.0, &mut Vec::new());
+    worker.load(save_game, 0).unwrap();
+
+    for tick in 0..90 {
+        worker.update(tick);
+    }
+
+    let messages: Vec<WorkerMessage> = channel_1_rx.try_iter().collect();
+    let events: Vec<_> = messages
+        .iter()
+        .filter_map(|message| match message {
+            WorkerMessage::BattleEvents(events) => Some(events),
+            _ => None,
+        })
+        .flatten()
+        .copied()
+        .collect();
+
+    // the casters are shown casting
+    let casting: Vec<usize> = messages
+        .iter()
+        .filter_map(|message| match message {
+            WorkerMessage::Snapshot(snapshot) => Some(&snapshot.ant_actions),
+            _ => None,
+        })
+        .flatten()
+        .filter(|action| matches!(action.action, AntAction::SetAnimation(AntAnimation::Cast)))
+        .map(|action| action.index)
+        .collect();
+    for id in [0, 2, 4, 5] {
+        assert!(casting.contains(&id), "{}", id);
+    }
+
+    // every ability is cast once, after its cast time
+    for (id, unit_type) in [
+        (0, "heavy_ant"),
+        (2, "medic_ant"),
+        (4, "leaper_ant"),
+        (5, "brood_ant"),
+    ] {
+        let ability = unit_definitions
+            .get(unit_type)
+            .unwrap()
+            .ability
+            .as_ref()
+            .unwrap();
+        let cast_ticks: Vec<u64> = events
+            .iter()
+            .filter(|event| {
+                matches!(event.kind, BattleEventKind::AbilityCast { unit_id, .. } if unit_id == id)
+            })
+            .map(|event| event.tick)
+            .collect();
+        assert_eq!(cast_ticks.len(), 1, "{}", unit_type);
+        assert!(cast_ticks[0] - start_tick >= unit_definition::seconds_to_ticks(ability.cast_time));
+    }
+
+    // the slam hurts and stuns the enemy next to it
+    assert!(events.iter().any(|event| matches!(
+        event.kind,
+        BattleEventKind::Hit { source_id: 0, target_id: 1, damage } if damage == 25.0
+    )));
+    assert!(events.iter().any(|event| matches!(
+        event.kind,
+        BattleEventKind::StatusEffectApplied {
+            source_id: 0,
+            target_id: 1,
+            ..
+        }
+    )));
+
+    let save_game = worker.save(90);
+    let ants = &save_game.ants;
+    let ant = |id| &ants[index_of(ants, id).unwrap()];
+
+    // the heal pulse heals the hurt ally
+    assert!(ant(3).controller.health() > 50.0);
+
+    // the dash jumps ahead, further than the leaper walks in the meantime
+    let moved = (ant(4).controller.get_position() - leaper_position).x;
+    assert!(moved > 8.0, "{}", moved);
+
+    // the summoned ants join the army of the brood ant
+    let summons: Vec<_> = ants.iter().filter(|ant| ant.id > 5).collect();
+    assert_eq!(summons.len(), 2);
+    assert!(
+        summons
+            .iter()
+            .all(|ant| ant.unit_type == "ant" && ant.faction == Faction::Blue)
+    );
+
+    // the cooldown keeps the caster from casting again, even with full mana
+    let mut heavy_ant = ant(0).controller.clone();
+    heavy_ant.gain_mana(1000.0);
+    assert!(!heavy_ant.is_ability_ready());
+}
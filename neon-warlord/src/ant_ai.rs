@@ -1,162 +1,147 @@
 //! Routines controlling an ant npc
 
-use cgmath::prelude::*;
+pub mod behaviour_tree;
 
-use crate::game_board::{self, Agent, Faction};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    ability::AbilityKind,
+    ant_ai::behaviour_tree::{Context, Node, NodeDefinition},
+    game_board::{self, Faction, TargetStrategy},
+    unit_definition::{self, UnitDefinition},
+};
 
 type Vec2 = cgmath::Vector2<f32>;
 
+#[derive(Clone, Serialize, Deserialize)]
 pub struct StateData {
-    pub target_agent: Option<Agent>,
+    pub target_id: Option<usize>,
+    pub target_age: u64, // ticks since the target has been acquired
 }
 
 impl StateData {
     pub fn new() -> Self {
-        Self { target_agent: None }
+        Self {
+            target_id: None,
+            target_age: 0,
+        }
     }
 }
 
+#[derive(Clone, Serialize, Deserialize)]
 pub struct AntAi {
     // consts
+    id: usize,
     faction: Faction,
     range: f32,
+    target_strategy: TargetStrategy,
+    retarget_cooldown: u64,
+    ability: Option<AbilityKind>,
 
     // vars
-    state: State,
+    brain: Node,
     state_data: StateData,
 }
 
 impl AntAi {
-    pub fn new(faction: Faction) -> Self {
-        let state = State::LookForEnemies;
-        let range = 10.0;
+    pub fn new(
+        id: usize,
+        faction: Faction,
+        definition: &UnitDefinition,
+        brain: &NodeDefinition,
+    ) -> Self {
+        let targeting = &definition.targeting;
+        let retarget_cooldown = unit_definition::seconds_to_ticks(targeting.retarget_cooldown);
+
+        let brain = Node::new(brain);
         let state_data = StateData::new();
 
         Self {
-            state,
+            id,
             faction,
-            range,
+            range: definition.stats.range,
+            target_strategy: targeting.strategy,
+            retarget_cooldown,
+            ability: definition
+                .ability
+                .as_ref()
+                .map(|ability| ability.kind.clone()),
+            brain,
             state_data,
         }
     }
 
-    pub fn update(&mut self, interface: &mut dyn AntBodyInterface, world: &mut dyn WorldInterface) {
-        match self.state {
-            State::LookForEnemies => {
-                // #######################################################
-                // get closest enemy
-                let position = interface.get_position();
-                let agents = world.world_get_agents();
-                let closest_enemy = self.get_closest_enemy(position, agents);
-
-                // state transition
-                if closest_enemy.is_some() {
-                    self.state_data.target_agent = closest_enemy;
-                    self.state = State::MoveUntilInRange;
-                }
-
-                self.state_data.target_agent = Some(Agent {
-                    faction: Faction::Red,
-                    position: Vec2::new(80.0, 00.0),
-                })
-            }
-            State::MoveToTarget => {
-                // #######################################################
-                // get positions
-                let position = interface.get_position();
-                let target_agent = self.state_data.target_agent.unwrap();
-                let target_agent_position = target_agent.position;
-
-                // check if in range
-                if self.is_in_range(position, target_agent_position) {
-                    self.state = State::ChargeShot;
-                    return;
-                }
-
-                let direction = (position - target_agent_position).normalize();
-                let target_position = target_agent_position + self.range * direction;
-
-                // state transition
-                interface.move_to(target_position);
-                self.state = State::MoveUntilInRange;
-            }
-            State::MoveUntilInRange => {
-                // #######################################################
-
-                // wait for finish moving
-                if interface.is_moving() {
-                    return;
-                }
-
-                // state transition
-                self.state = State::MoveToTarget;
-            }
-            State::ChargeShot => {
-                // #######################################################
-                interface.charge_shot();
-                self.state = State::WaitUntilShotCharged;
-            }
-            State::WaitUntilShotCharged => {
-                // #######################################################
-                if interface.is_shot_ready() {
-                    self.state = State::Shoot;
-                }
-            }
-            State::Shoot => {
-                // #######################################################
-                interface.shoot();
-                self.state = State::LookForEnemies;
-            }
-        }
+    pub fn target_id(&self) -> Option<usize> {
+        self.state_data.target_id
     }
 
-    fn get_closest_enemy(&self, position: Vec2, agents: &[Agent]) -> Option<Agent> {
-        let mut distance = f32::MAX;
-        let mut res = None;
-
-        for agent in agents {
-            if agent.faction != self.faction {
-                let agent_distance = (agent.position - position).magnitude();
-
-                if agent_distance < distance {
-                    distance = agent_distance;
-                    res = Some(*agent);
-                }
-            }
-        }
-
-        res
+    pub fn update(&mut self, interface: &mut dyn AntBodyInterface, world: &dyn WorldInterface) {
+        self.state_data.target_age = self.state_data.target_age.saturating_add(1);
+
+        let mut context = Context {
+            id: self.id,
+            faction: self.faction,
+            range: self.range,
+            target_strategy: self.target_strategy,
+            retarget_cooldown: self.retarget_cooldown,
+            ability: self.ability.as_ref(),
+            state_data: &mut self.state_data,
+            body: interface,
+            world,
+        };
+
+        self.brain.tick(&mut context);
     }
-
-    fn is_in_range(
-        &self,
-        position: cgmath::Vector2<f32>,
-        target_agent_position: cgmath::Vector2<f32>,
-    ) -> bool {
-        (target_agent_position - position).magnitude() <= self.range
-    }
-}
-
-enum State {
-    LookForEnemies,
-    MoveToTarget,
-    MoveUntilInRange,
-    ChargeShot,
-    WaitUntilShotCharged,
-    Shoot,
 }
 
 pub trait AntBodyInterface {
     fn get_position(&self) -> Vec2;
+    fn get_health_fraction(&self) -> f32; // between 0.0 and 1.0
     fn move_to(&mut self, target_position: Vec2);
     fn is_moving(&self) -> bool;
+    fn stop(&mut self); // stays where it is
     fn charge_shot(&mut self);
+    fn is_charging(&self) -> bool; // a stun or a cast interrupts the charge
     fn is_shot_ready(&self) -> bool;
-    fn shoot(&mut self);
+    fn shoot(&mut self, target_position: Vec2);
+    fn is_ability_ready(&self) -> bool; // the mana is full and the cooldown has passed
+    fn cast_ability(&mut self, target_position: Vec2);
+    fn is_casting(&self) -> bool;
 }
 
 pub trait WorldInterface {
-    fn world_get_agents(&self) -> &[game_board::Agent];
+    fn world_get_agent(&self, id: usize) -> Option<&game_board::Agent>;
+    fn world_get_nearest_enemy(
+        &self,
+        position: Vec2,
+        faction: Faction,
+    ) -> Option<&game_board::Agent>;
+    /// Appends all agents within the radius, dead ones included
+    fn world_get_agents_in_radius(
+        &self,
+        position: Vec2,
+        radius: f32,
+        agents: &mut Vec<game_board::Agent>,
+    );
+    /// Appends all agents within the radius and half_angle around the direction, a zero direction
+    /// has no cone and appends all agents within the radius
+    #[allow(unused)]
+    fn world_get_agents_in_cone(
+        &self,
+        position: Vec2,
+        direction: Vec2,
+        half_angle: cgmath::Rad<f32>,
+        radius: f32,
+        agents: &mut Vec<game_board::Agent>,
+    );
+    /// The living enemy within the radius of the seeker which fits the strategy best, the closest
+    /// enemy if none is within the radius
+    fn world_find_target(
+        &self,
+        seeker_id: usize,
+        strategy: TargetStrategy,
+        radius: f32,
+    ) -> Option<&game_board::Agent>;
 }
 
 // pub trait AntAiInterface: AntBodyInterface + WorldInterface {}
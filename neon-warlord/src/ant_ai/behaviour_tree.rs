@@ -0,0 +1,703 @@
+//! Behaviour trees composed from reusable nodes, the brains are defined in res/units.json
+
+use cgmath::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    ability::AbilityKind,
+    ant_ai::{AntBodyInterface, StateData, WorldInterface},
+    ant_controller::SEPARATION_RADIUS,
+    game_board::{Agent, Faction, TargetStrategy},
+};
+
+type Vec2 = cgmath::Vector2<f32>;
+
+/// Distance an ant runs away from its closest enemy per retreat
+const RETREAT_DISTANCE: f32 = 8.0;
+
+/// Health fraction of an ally below which a heal is worth casting
+const HEAL_THRESHOLD: f32 = 0.7;
+
+/// Multiple of the range within which the enemies are compared by the target strategy
+const AGGRO_RANGE_FACTOR: f32 = 3.0;
+
+/// Spots on each side of the way to the target tried before an ant shares a spot
+const NR_APPROACH_SPOTS: usize = 8;
+
+/// A node of a brain as written in units.json
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case", deny_unknown_fields)]
+pub enum NodeDefinition {
+    Sequence(Vec<NodeDefinition>), // runs the children in order until one does not succeed
+    Selector(Vec<NodeDefinition>), // runs the first child which does not fail, checked every tick
+    FindTarget,
+    ApproachToRange,
+    Kite { min_distance: f32 },
+    RetreatWhenLow { health: f32 }, // health between 0.0 and 1.0
+    ChargeAndShoot,
+    CastAbility, // fails if the ability is not ready or would be wasted
+}
+
+impl NodeDefinition {
+    pub fn validate(&self) -> Result<(), String> {
+        match self {
+            NodeDefinition::Sequence(children) | NodeDefinition::Selector(children) => {
+                if children.is_empty() {
+                    return Err("sequence and selector need at least one child".to_string());
+                }
+                for child in children {
+                    child.validate()?;
+                }
+            }
+            NodeDefinition::Kite { min_distance } => {
+                if !min_distance.is_finite() || *min_distance <= 0.0 {
+                    return Err(format!(
+                        "kite.min_distance must be positive, found {}",
+                        min_distance
+                    ));
+                }
+            }
+            NodeDefinition::RetreatWhenLow { health } => {
+                if !(*health > 0.0 && *health <= 1.0) {
+                    return Err(format!(
+                        "retreat_when_low.health must be in (0.0, 1.0], found {}",
+                        health
+                    ));
+                }
+            }
+            NodeDefinition::FindTarget
+            | NodeDefinition::ApproachToRange
+            | NodeDefinition::ChargeAndShoot
+            | NodeDefinition::CastAbility => {}
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Status {
+    Success,
+    Failure,
+    Running,
+}
+
+/// Everything the nodes of one ant work on during a tick
+pub struct Context<'a> {
+    pub id: usize,
+    pub faction: Faction,
+    pub range: f32,
+    pub target_strategy: TargetStrategy,
+    pub retarget_cooldown: u64, // ticks a target is kept at least
+    pub ability: Option<&'a AbilityKind>,
+    pub state_data: &'a mut StateData,
+    pub body: &'a mut dyn AntBodyInterface,
+    pub world: &'a dyn WorldInterface,
+}
+
+impl Context<'_> {
+    fn target_agent(&self) -> Option<Agent> {
+        let target_id = self.state_data.target_id?;
+
+        match self.world.world_get_agent(target_id) {
+            Some(agent) if agent.is_alive => Some(*agent),
+            _ => None,
+        }
+    }
+
+    /// Where the ability is worth casting, None if it would be wasted
+    fn ability_target_position(&self, ability: &AbilityKind) -> Option<Vec2> {
+        let position = self.body.get_position();
+
+        match ability {
+            AbilityKind::Slam { radius, .. } => {
+                let mut agents = Vec::new();
+                self.world
+                    .world_get_agents_in_radius(position, *radius, &mut agents);
+                agents
+                    .iter()
+                    .any(|agent| agent.is_alive && agent.faction != self.faction)
+                    .then_some(position)
+            }
+            AbilityKind::HealPulse { radius, .. } => {
+                let mut agents = Vec::new();
+                self.world
+                    .world_get_agents_in_radius(position, *radius, &mut agents);
+                agents
+                    .iter()
+                    .any(|agent| {
+                        agent.is_alive
+                            && agent.faction == self.faction
+                            && agent.health < HEAL_THRESHOLD * agent.max_health
+                    })
+                    .then_some(position)
+            }
+            AbilityKind::Dash { .. } => {
+                // close the gap to the target
+                let target = self.target_agent()?;
+                let offset = position - target.position;
+                let distance = offset.magnitude();
+                (distance > self.range).then(|| target.position + self.range * offset / distance)
+            }
+            AbilityKind::Summon { .. } => self
+                .world
+                .world_get_nearest_enemy(position, self.faction)
+                .map(|_| position),
+        }
+    }
+
+    /// The spot at the distance around the center closest to the direction with no other ant
+    /// near it, the spots are tried alternating on both sides
+    fn free_spot(&self, center: Vec2, direction: Vec2, distance: f32) -> Vec2 {
+        // neighbouring spots are as far apart along the circle as an ant has to stay away
+        let step = if distance > 0.0 {
+            2.0 * SEPARATION_RADIUS / distance
+        } else {
+            0.0
+        };
+
+        let mut agents = Vec::new();
+        for i in 0..=2 * NR_APPROACH_SPOTS {
+            let side = if i % 2 == 0 { 1.0 } else { -1.0 };
+            let (sin, cos) = (step * i.div_ceil(2) as f32 * side).sin_cos();
+            let rotated = Vec2::new(
+                direction.x * cos - direction.y * sin,
+                direction.x * sin + direction.y * cos,
+            );
+            let spot = center + distance * rotated;
+
+            agents.clear();
+            self.world
+                .world_get_agents_in_radius(spot, 2.0 * SEPARATION_RADIUS, &mut agents);
+            if agents
+                .iter()
+                .all(|agent| agent.id == self.id || !agent.is_alive)
+            {
+                return spot;
+            }
+        }
+
+        center + distance * direction
+    }
+}
+
+/// A node together with its state while it is running
+#[derive(Clone, Serialize, Deserialize)]
+pub enum Node {
+    Sequence {
+        children: Vec<Node>,
+        running: usize,
+    },
+    Selector {
+        children: Vec<Node>,
+        running: Option<usize>,
+    },
+    FindTarget,
+    ApproachToRange,
+    Kite {
+        min_distance: f32,
+    },
+    RetreatWhenLow {
+        health: f32,
+    },
+    ChargeAndShoot {
+        is_charging: bool,
+    },
+    CastAbility {
+        is_casting: bool,
+    },
+}
+
+impl Node {
+    pub fn new(definition: &NodeDefinition) -> Self {
+        match definition {
+            NodeDefinition::Sequence(children) => Node::Sequence {
+                children: children.iter().map(Node::new).collect(),
+                running: 0,
+            },
+            NodeDefinition::Selector(children) => Node::Selector {
+                children: children.iter().map(Node::new).collect(),
+                running: None,
+            },
+            NodeDefinition::FindTarget => Node::FindTarget,
+            NodeDefinition::ApproachToRange => Node::ApproachToRange,
+            NodeDefinition::Kite { min_distance } => Node::Kite {
+                min_distance: *min_distance,
+            },
+            NodeDefinition::RetreatWhenLow { health } => Node::RetreatWhenLow { health: *health },
+            NodeDefinition::ChargeAndShoot => Node::ChargeAndShoot { is_charging: false },
+            NodeDefinition::CastAbility => Node::CastAbility { is_casting: false },
+        }
+    }
+
+    /// Forgets the progress of the node and all its children
+    pub fn reset(&mut self) {
+        match self {
+            Node::Sequence { children, running } => {
+                children.iter_mut().for_each(Node::reset);
+                *running = 0;
+            }
+            Node::Selector { children, running } => {
+                children.iter_mut().for_each(Node::reset);
+                *running = None;
+            }
+            Node::ChargeAndShoot { is_charging } => {
+                *is_charging = false;
+            }
+            Node::CastAbility { is_casting } => {
+                *is_casting = false;
+            }
+            Node::FindTarget
+            | Node::ApproachToRange
+            | Node::Kite { .. }
+            | Node::RetreatWhenLow { .. } => {}
+        }
+    }
+
+    pub fn tick(&mut self, context: &mut Context) -> Status {
+        match self {
+            // #######################################################
+            Node::Sequence { children, running } => {
+                while *running < children.len() {
+                    match children[*running].tick(context) {
+                        Status::Success => *running += 1,
+                        Status::Running => return Status::Running,
+                        Status::Failure => {
+                            *running = 0;
+                            return Status::Failure;
+                        }
+                    }
+                }
+
+                *running = 0;
+                Status::Success
+            }
+            // #######################################################
+            Node::Selector { children, running } => {
+                let mut status = Status::Failure;
+                let mut selected = None;
+                for (i, child) in children.iter_mut().enumerate() {
+                    status = child.tick(context);
+                    if status != Status::Failure {
+                        selected = Some(i);
+                        break;
+                    }
+                }
+
+                // a child with a higher priority interrupts the running one
+                if let Some(previous) = *running
+                    && Some(previous) != selected
+                {
+                    children[previous].reset();
+                }
+
+                *running = if status == Status::Running {
+                    selected
+                } else {
+                    None
+                };
+                status
+            }
+            // #######################################################
+            Node::FindTarget => {
+                // a sticky target is kept until the cooldown has passed
+                if context.state_data.target_age < context.retarget_cooldown
+                    && context.target_agent().is_some()
+                {
+                    return Status::Success;
+                }
+
+                let aggro_range = context.range * AGGRO_RANGE_FACTOR;
+                let Some(enemy) = context.world.world_find_target(
+                    context.id,
+                    context.target_strategy,
+                    aggro_range,
+                ) else {
+                    return Status::Failure;
+                };
+
+                if context.state_data.target_id != Some(enemy.id) {
+                    context.state_data.target_id = Some(enemy.id);
+                    context.state_data.target_age = 0;
+                }
+                Status::Success
+            }
+            // #######################################################
+            Node::ApproachToRange => {
+                let Some(target) = context.target_agent() else {
+                    return Status::Failure;
+                };
+
+                // the ants stop inside the range by the separation, the ants behind them and the
+                // ones parked short of a spot already taken are still in range
+                let spot_distance = (context.range - SEPARATION_RADIUS).max(0.0);
+                let position = context.body.get_position();
+                let offset = position - target.position;
+                let distance = offset.magnitude();
+                if distance <= spot_distance {
+                    context.body.stop();
+                    return Status::Success;
+                }
+
+                // wait for finish moving
+                if context.body.is_moving() {
+                    return Status::Running;
+                }
+
+                if distance <= context.range {
+                    return Status::Success;
+                }
+
+                let direction = if distance > 0.0 {
+                    offset / distance
+                } else {
+                    Vec2::unit_x()
+                };
+                let spot = context.free_spot(target.position, direction, spot_distance);
+                context.body.move_to(spot);
+                Status::Running
+            }
+            // #######################################################
+            Node::Kite { min_distance } => {
+                if context.body.is_moving() {
+                    return Status::Running;
+                }
+
+                let Some(target) = context.target_agent() else {
+                    return Status::Failure;
+                };
+
+                let offset = context.body.get_position() - target.position;
+                let distance = offset.magnitude();
+                if distance >= *min_distance {
+                    return Status::Success;
+                }
+
+                let direction = if distance > 0.0 {
+                    offset / distance
+                } else {
+                    Vec2::unit_x()
+                };
+                context
+                    .body
+                    .move_to(target.position + *min_distance * direction);
+                Status::Running
+            }
+            // #######################################################
+            Node::RetreatWhenLow { health } => {
+                if context.body.get_health_fraction() >= *health {
+                    return Status::Failure;
+                }
+
+                // fight back once no enemy is within range
+                let position = context.body.get_position();
+                let Some(enemy) = context
+                    .world
+                    .world_get_nearest_enemy(position, context.faction)
+                else {
+                    return Status::Failure;
+                };
+                let offset = position - enemy.position;
+                let distance = offset.magnitude();
+                if distance > context.range {
+                    return Status::Failure;
+                }
+
+                if context.body.is_moving() {
+                    return Status::Running;
+                }
+
+                let direction = if distance > 0.0 {
+                    offset / distance
+                } else {
+                    Vec2::unit_x()
+                };
+                context
+                    .body
+                    .move_to(position + RETREAT_DISTANCE * direction);
+                Status::Running
+            }
+            // #######################################################
+            Node::ChargeAndShoot { is_charging } => {
+                let Some(target) = context.target_agent() else {
+                    *is_charging = false;
+                    return Status::Failure;
+                };
+
+                // the charge starts over once a stun or a cast has interrupted it
+                let is_interrupted = !context.body.is_charging() && !context.body.is_shot_ready();
+                if !*is_charging || is_interrupted {
+                    context.body.charge_shot();
+                    *is_charging = true;
+                    return Status::Running;
+                }
+
+                if !context.body.is_shot_ready() {
+                    return Status::Running;
+                }
+
+                context.body.shoot(target.position);
+                *is_charging = false;
+                Status::Success
+            }
+            // #######################################################
+            Node::CastAbility { is_casting } => {
+                if *is_casting {
+                    if context.body.is_casting() {
+                        return Status::Running;
+                    }
+                    *is_casting = false;
+                    return Status::Success;
+                }
+
+                if !context.body.is_ability_ready() {
+                    return Status::Failure;
+                }
+                let Some(ability) = context.ability else {
+                    return Status::Failure;
+                };
+                let Some(target_position) = context.ability_target_position(ability) else {
+                    return Status::Failure;
+                };
+
+                context.body.cast_ability(target_position);
+                *is_casting = true;
+                Status::Running
+            }
+        }
+    }
+}
+
+/// A body standing where it is, its charge takes a few ticks and a stun keeps it from charging
+#[cfg(test)]
+struct TestBody {
+    position: Vec2,
+    health: f32,
+    target_position: Option<Vec2>, // where it was last sent to
+    is_stunned: bool,
+    charge: Option<u32>, // ticks until the shot is ready
+    nr_shots: usize,
+}
+
+#[cfg(test)]
+impl TestBody {
+    fn new(position: Vec2) -> Self {
+        Self {
+            position,
+            health: 1.0,
+            target_position: None,
+            is_stunned: false,
+            charge: None,
+            nr_shots: 0,
+        }
+    }
+
+    /// Advances the charge by a tick
+    fn update(&mut self) {
+        if let Some(ticks) = &mut self.charge {
+            *ticks = ticks.saturating_sub(1);
+        }
+    }
+}
+
+#[cfg(test)]
+impl AntBodyInterface for TestBody {
+    fn get_position(&self) -> Vec2 {
+        self.position
+    }
+    fn get_health_fraction(&self) -> f32 {
+        self.health
+    }
+    fn move_to(&mut self, target_position: Vec2) {
+        self.target_position = Some(target_position);
+    }
+    fn is_moving(&self) -> bool {
+        false
+    }
+    fn stop(&mut self) {}
+    fn charge_shot(&mut self) {
+        if !self.is_stunned {
+            self.charge = Some(3);
+        }
+    }
+    fn is_charging(&self) -> bool {
+        self.charge.is_some_and(|ticks| ticks > 0)
+    }
+    fn is_shot_ready(&self) -> bool {
+        self.charge == Some(0)
+    }
+    fn shoot(&mut self, _target_position: Vec2) {
+        if self.is_shot_ready() {
+            self.charge = None;
+            self.nr_shots += 1;
+        }
+    }
+    fn is_ability_ready(&self) -> bool {
+        false
+    }
+    fn cast_ability(&mut self, _target_position: Vec2) {}
+    fn is_casting(&self) -> bool {
+        false
+    }
+}
+
+#[test]
+fn test_retreat_interrupts_the_attack() {
+    let unit_definitions = crate::unit_definition::UnitDefinitions::load_default().unwrap();
+    let mut brain = Node::new(unit_definitions.brain("skirmisher").unwrap());
+
+    let mut agents = Vec::new();
+    for (id, faction, position) in [
+        (0, Faction::Blue, Vec2::new(0.0, 0.0)),
+        (1, Faction::Red, Vec2::new(10.0, 0.0)),
+    ] {
+        agents.push(Agent {
+            id,
+            faction,
+            position,
+            is_alive: true,
+            health: 100.0,
+            max_health: 100.0,
+            threat: 1.0,
+            last_attacker_id: None,
+        });
+    }
+    let mut game_board = crate::game_board::GameBoard::new();
+    game_board.set_agents(agents);
+
+    let mut body = TestBody::new(Vec2::new(0.0, 0.0));
+    let mut state_data = StateData::new();
+
+    // healthy, the skirmisher stays in range and charges its shot
+    let mut context = Context {
+        id: 0,
+        faction: Faction::Blue,
+        range: 12.0,
+        target_strategy: TargetStrategy::Closest,
+        retarget_cooldown: 0,
+        ability: None,
+        state_data: &mut state_data,
+        body: &mut body,
+        world: &game_board,
+    };
+    assert_eq!(brain.tick(&mut context), Status::Running);
+    assert_eq!(context.state_data.target_id, Some(1));
+    assert!(matches!(
+        brain,
+        Node::Selector {
+            running: Some(1),
+            ..
+        }
+    ));
+
+    // hurt, it runs away from the enemy
+    body.health = 0.2;
+    let mut context = Context {
+        id: 0,
+        faction: Faction::Blue,
+        range: 12.0,
+        target_strategy: TargetStrategy::Closest,
+        retarget_cooldown: 0,
+        ability: None,
+        state_data: &mut state_data,
+        body: &mut body,
+        world: &game_board,
+    };
+    assert_eq!(brain.tick(&mut context), Status::Running);
+    assert!(matches!(
+        brain,
+        Node::Selector {
+            running: Some(0),
+            ..
+        }
+    ));
+    assert!(body.target_position.unwrap().x < 0.0);
+}
+
+#[test]
+fn test_charge_starts_over_after_a_stun() {
+    let mut game_board = crate::game_board::GameBoard::new();
+    game_board.set_agents(vec![Agent {
+        id: 1,
+        faction: Faction::Red,
+        position: Vec2::new(10.0, 0.0),
+        is_alive: true,
+        health: 100.0,
+        max_health: 100.0,
+        threat: 1.0,
+        last_attacker_id: None,
+    }]);
+
+    let mut brain = Node::new(&NodeDefinition::ChargeAndShoot);
+    let mut body = TestBody::new(Vec2::new(0.0, 0.0));
+    let mut state_data = StateData::new();
+    state_data.target_id = Some(1);
+
+    for tick in 0..20 {
+        // stunned in the middle of the charge, the controller drops it
+        if tick == 2 {
+            body.is_stunned = true;
+            body.charge = None;
+        }
+        if tick == 5 {
+            body.is_stunned = false;
+        }
+
+        let mut context = Context {
+            id: 0,
+            faction: Faction::Blue,
+            range: 12.0,
+            target_strategy: TargetStrategy::Closest,
+            retarget_cooldown: 0,
+            ability: None,
+            state_data: &mut state_data,
+            body: &mut body,
+            world: &game_board,
+        };
+        brain.tick(&mut context);
+        body.update();
+    }
+
+    assert!(body.nr_shots > 0);
+}
+
+#[test]
+fn test_ants_approaching_from_the_same_side_all_shoot() {
+    use crate::{
+        ant_unit::AntUnit, game_board::GameBoard, heightmap_generator::HeightMapGenerator,
+        navigation::NavigationGrid, unit_definition::UnitDefinitions,
+    };
+
+    let unit_definitions = UnitDefinitions::load_default().unwrap();
+    let terrain = HeightMapGenerator::new();
+    let mut navigation = NavigationGrid::new();
+
+    // a column of ants behind each other heading for the same enemy, which stands still
+    let mut ants: Vec<AntUnit> = (0..6)
+        .map(|id| {
+            let position = Vec2::new(-2.0 * id as f32, 0.0);
+            AntUnit::new(id, "ant", position, Faction::Blue, &unit_definitions).unwrap()
+        })
+        .collect();
+    let enemy = Vec2::new(30.0, 0.0);
+    ants.push(AntUnit::new(6, "heavy_ant", enemy, Faction::Red, &unit_definitions).unwrap());
+
+    let mut game_board = GameBoard::new();
+    let mut has_shot = [false; 6];
+    let mut actions = Vec::new();
+    for tick in 0..60 * 30 {
+        game_board.update(&ants);
+
+        for ant in &mut ants[..6] {
+            ant.ai.update(&mut ant.controller, &game_board);
+            ant.controller
+                .update(tick, &terrain, &mut navigation, &game_board, &mut actions);
+
+            if ant.controller.take_shot().is_some() {
+                has_shot[ant.id] = true;
+            }
+        }
+    }
+
+    assert_eq!(has_shot, [true; 6]);
+}
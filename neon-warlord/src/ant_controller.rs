@@ -1,16 +1,39 @@
 //! Implements the WorldInterface from AntAi
 
-use cgmath::{InnerSpace, Zero};
-
-use crate::{ant_ai::AntBodyInterface, worker::interpolated_position::InterpolatedPosition};
+use cgmath::InnerSpace;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    ability::{Ability, AbilityKind, Cast, MANA_PER_DAMAGE_TAKEN},
+    ant_ai::{AntBodyInterface, WorldInterface},
+    combat::{Hit, Shot},
+    game_board::Agent,
+    heightmap_generator::HeightMapGenerator,
+    navigation::NavigationGrid,
+    status_effect::{StatusEffect, StatusEffectSet, StatusEffects},
+    unit_definition::{self, ProjectileDefinition, UnitDefinition},
+    worker::interpolated_position::InterpolatedPosition,
+};
 
 type Vec2 = cgmath::Vector2<f32>;
+type Vec3 = cgmath::Vector3<f32>;
+
+/// Loss of speed per unit of rise along the way, walking downhill gains the same
+const SLOPE_SLOWDOWN: f32 = 0.5;
+const MIN_SLOPE_SPEED: f32 = 0.3; // fraction of the speed on the steepest climb
+const MAX_SLOPE_SPEED: f32 = 1.3; // fraction of the speed on the steepest descent
+
+/// Distance below which moving ants push each other apart
+pub const SEPARATION_RADIUS: f32 = 1.5;
+const SEPARATION_WEIGHT: f32 = 2.0; // strength of the push relative to the way to the waypoint
+const MIN_PROGRESS: f32 = 0.1; // fraction of the speed below which the pushed ant is blocked
 
-#[derive(Clone, Copy, PartialEq)]
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
 pub enum AntAnimation {
     Idle,
     Walk,
-    _ChargeShot,
+    ChargeShot,
+    Cast,
 }
 
 #[derive(Clone, Copy, Debug)]
@@ -21,9 +44,9 @@ pub struct AntPosition {
 }
 
 impl AntPosition {
-    pub fn new() -> Self {
-        let pos = InterpolatedPosition::zero();
-        let look_at = cgmath::Vector3::zero();
+    pub fn new(pos: cgmath::Vector3<f32>) -> Self {
+        let pos = InterpolatedPosition::new(pos, instant::Instant::now());
+        let look_at = cgmath::Vector3::unit_x();
         let is_final = true;
 
         Self {
@@ -38,15 +61,25 @@ impl AntPosition {
 pub struct AntPositionSnapshot {
     pub pos: cgmath::Vector3<f32>,
     pub look_at: cgmath::Vector3<f32>,
-    pub time_stamp: instant::Instant,
+}
+
+#[derive(Clone, Copy)]
+pub struct AntSpawnSnapshot {
+    pub pos: cgmath::Vector3<f32>,
+    pub look_at: cgmath::Vector3<f32>,
+    pub unit_type: usize, // index into the unit definitions
 }
 
 #[derive(Clone, Copy)]
 pub enum AntAction {
+    Spawn(AntSpawnSnapshot),
     UpdatePosition(AntPositionSnapshot),
     FinalPosition(AntPositionSnapshot),
     SetAnimation(AntAnimation),
     SetAnimationSpeed(f32),
+    SetHealth(f32), // remaining health between 0.0 and 1.0
+    SetStatusEffects(StatusEffectSet),
+    Die,
 }
 
 #[derive(Clone, Copy)]
@@ -65,15 +98,10 @@ impl AntActionStruct {
     pub fn update_position(
         pos: cgmath::Vector3<f32>,
         look_at: cgmath::Vector3<f32>,
-        time_stamp: instant::Instant,
         index: usize,
     ) -> Self {
         Self {
-            action: AntAction::UpdatePosition(AntPositionSnapshot {
-                pos,
-                look_at,
-                time_stamp,
-            }),
+            action: AntAction::UpdatePosition(AntPositionSnapshot { pos, look_at }),
             index,
         }
     }
@@ -81,80 +109,454 @@ impl AntActionStruct {
     pub fn final_position(
         pos: cgmath::Vector3<f32>,
         look_at: cgmath::Vector3<f32>,
-        time_stamp: instant::Instant,
         index: usize,
     ) -> Self {
         Self {
-            action: AntAction::FinalPosition(AntPositionSnapshot {
-                pos,
-                look_at,
-                time_stamp,
-            }),
+            action: AntAction::FinalPosition(AntPositionSnapshot { pos, look_at }),
             index,
         }
     }
 }
 
-#[derive(PartialEq)]
+#[derive(Clone, Copy, PartialEq, Serialize, Deserialize)]
 enum State {
     Idle,
     Move,
+    StartChargeShot,
     ChargeShot,
     ShotCharged,
     Shoot,
+    StartCast,
+    Cast,
+    Dead,
 }
 
+#[derive(Clone, Serialize, Deserialize)]
 pub struct AntController {
     // pub game_board: &'a GameBoard,
     // pub ant_state: &'a mut AntState,
     position: cgmath::Vector2<f32>,
     target_position: cgmath::Vector2<f32>,
+    path: Option<Vec<Vec2>>, // remaining waypoints with the next one last, planned on the next update
 
     animation: AntAnimation,
 
     state: State,
 
     index: usize,
+    unit_type: usize,
+    #[serde(skip)] // a loaded ant is spawned again on the render side
+    is_spawned: bool,
+
+    // Stats
+    max_health: f32,
+    speed: f32,
+    charge_duration: u64,
+    shot_damage: f32,
+    projectile: ProjectileDefinition,
+
+    // Combat
+    health: f32,
+    is_alive: bool,
+    status_effects: StatusEffects,
+    #[serde(skip)] // a loaded ant shows its effects again
+    shown_status_effects: StatusEffectSet,
+
+    // State::ChargeShot
+    start_charge_tick: u64,
+
+    // State::Shoot
+    shot_target_position: cgmath::Vector2<f32>,
+    shot: Option<Shot>,
+
+    // Ability
+    ability: Option<Ability>,
+    mana: f32,
+    ability_cooldown: u64, // ticks until the ability can be cast again
+
+    // State::Cast
+    start_cast_tick: u64,
+    cast_target_position: cgmath::Vector2<f32>,
+    cast: Option<Cast>,
+
+    #[serde(skip)] // stopped while moving, the final position is sent on the next update
+    is_stopping: bool,
+    #[serde(skip)] // reused buffer for the neighbour queries
+    neighbours: Vec<Agent>,
 }
 
 impl AntController {
-    pub fn new(position: cgmath::Vector2<f32>, index: usize) -> Self {
+    pub fn new(
+        position: cgmath::Vector2<f32>,
+        index: usize,
+        unit_type: usize,
+        definition: &UnitDefinition,
+    ) -> Self {
         let target_position = position;
         let animation = AntAnimation::Idle;
         let state = State::Idle;
 
+        let stats = &definition.stats;
+        let speed = unit_definition::per_second_to_per_tick(stats.speed);
+        let charge_duration = unit_definition::seconds_to_ticks(stats.charge_duration);
+
+        let health = stats.max_health;
+        let is_alive = true;
+
         Self {
             position,
             target_position,
+            path: None,
             animation,
             state,
             index,
+            unit_type,
+            is_spawned: false,
+            max_health: stats.max_health,
+            speed,
+            charge_duration,
+            shot_damage: stats.shot_damage,
+            projectile: definition.projectile,
+            health,
+            is_alive,
+            status_effects: StatusEffects::new(),
+            shown_status_effects: StatusEffectSet::default(),
+            start_charge_tick: 0,
+            shot_target_position: position,
+            shot: None,
+            ability: definition.ability.as_ref().map(Ability::new),
+            mana: 0.0,
+            ability_cooldown: 0,
+            start_cast_tick: 0,
+            cast_target_position: position,
+            cast: None,
+            is_stopping: false,
+            neighbours: Vec::new(),
         }
     }
 
-    pub fn update(&mut self, time_stamp: &instant::Instant, actions: &mut Vec<AntActionStruct>) {
+    pub fn is_alive(&self) -> bool {
+        self.is_alive
+    }
+
+    /// Returns the shot fired during the last update
+    pub fn take_shot(&mut self) -> Option<Shot> {
+        self.shot.take()
+    }
+
+    /// Returns the ability cast during the last update, a dash is already done
+    pub fn take_cast(&mut self) -> Option<Cast> {
+        self.cast.take()
+    }
+
+    /// The mana is full once it reaches the cost of the ability
+    pub fn gain_mana(&mut self, mana: f32) {
+        if let Some(ability) = &self.ability
+            && self.is_alive
+        {
+            self.mana = (self.mana + mana).min(ability.mana_cost);
+        }
+    }
+
+    pub fn heal(&mut self, health: f32, actions: &mut Vec<AntActionStruct>) {
+        if !self.is_alive {
+            return;
+        }
+
+        self.health = (self.health + health).min(self.max_health);
+        actions.push(AntActionStruct {
+            action: AntAction::SetHealth(self.health / self.max_health),
+            index: self.index,
+        });
+    }
+
+    pub fn health(&self) -> f32 {
+        self.health
+    }
+
+    pub fn max_health(&self) -> f32 {
+        self.max_health
+    }
+
+    /// Makes the unit tougher, only used before it has been spawned
+    pub fn scale(&mut self, health_factor: f32, damage_factor: f32) {
+        self.max_health *= health_factor;
+        self.health = self.max_health;
+        self.shot_damage *= damage_factor;
+    }
+
+    /// Damage per tick while shooting continuously
+    pub fn threat(&self) -> f32 {
+        self.shot_damage / self.charge_duration.max(1) as f32
+    }
+
+    /// Returns the damage which has actually been applied
+    pub fn apply_damage(&mut self, damage: f32, actions: &mut Vec<AntActionStruct>) -> f32 {
+        if !self.is_alive {
+            return 0.0;
+        }
+
+        let damage = self.status_effects.absorb(damage);
+        let applied_damage = damage.min(self.health);
+        self.health -= applied_damage;
+        self.gain_mana(applied_damage * MANA_PER_DAMAGE_TAKEN);
+        actions.push(AntActionStruct {
+            action: AntAction::SetHealth(self.health / self.max_health),
+            index: self.index,
+        });
+
+        if self.health <= 0.0 {
+            self.is_alive = false;
+            self.state = State::Dead;
+            self.status_effects.clear();
+            actions.push(AntActionStruct {
+                action: AntAction::Die,
+                index: self.index,
+            });
+        }
+
+        applied_damage
+    }
+
+    pub fn apply_status_effect(&mut self, effect: StatusEffect) {
+        if self.is_alive {
+            self.status_effects.apply(effect);
+        }
+    }
+
+    /// Lets the burns deal their damage and the effects expire
+    pub fn update_status_effects(
+        &mut self,
+        hits: &mut Vec<Hit>,
+        actions: &mut Vec<AntActionStruct>,
+    ) {
+        // the effects are cleared on death, the dead ant keeps its color
+        if !self.is_alive {
+            return;
+        }
+
+        for burn in self.status_effects.burns() {
+            hits.push(Hit {
+                source_id: burn.source_id,
+                target_id: self.index,
+                damage: burn.strength,
+                effect: None,
+            });
+        }
+        self.status_effects.update();
+
+        let status_effects = self.status_effects.kinds();
+        if self.shown_status_effects != status_effects {
+            self.shown_status_effects = status_effects;
+            actions.push(AntActionStruct {
+                action: AntAction::SetStatusEffects(status_effects),
+                index: self.index,
+            });
+        }
+    }
+
+    /// A spawned ant is shown fresh, a loaded ant shows again how far it got
+    fn send_restored_state(&mut self, actions: &mut Vec<AntActionStruct>) {
+        let mut push = |action| {
+            actions.push(AntActionStruct {
+                action,
+                index: self.index,
+            })
+        };
+
+        if self.health < self.max_health {
+            push(AntAction::SetHealth(self.health / self.max_health));
+        }
+        if !self.is_alive {
+            push(AntAction::Die);
+            return;
+        }
+
+        if self.animation != AntAnimation::Idle {
+            push(AntAction::SetAnimation(self.animation));
+            if self.animation == AntAnimation::Walk {
+                push(AntAction::SetAnimationSpeed(self.speed));
+            }
+        }
+
+        self.shown_status_effects = self.status_effects.kinds();
+        if self.shown_status_effects != StatusEffectSet::default() {
+            push(AntAction::SetStatusEffects(self.shown_status_effects));
+        }
+    }
+
+    fn set_animation(&mut self, animation: AntAnimation, actions: &mut Vec<AntActionStruct>) {
+        if self.animation != animation {
+            self.animation = animation;
+            actions.push(AntActionStruct::from_animation(animation, self.index));
+        }
+    }
+
+    /// Looks along the direction, pitched to follow the slope of the terrain
+    fn look_at(terrain: &HeightMapGenerator, position: Vec2, direction: Vec2) -> Vec3 {
+        let rise = terrain.gradient(position).dot(direction);
+
+        direction.extend(rise).normalize()
+    }
+
+    /// Lets the ant stand still where it is, facing where it was heading
+    fn send_final_position(
+        &self,
+        terrain: &HeightMapGenerator,
+        actions: &mut Vec<AntActionStruct>,
+    ) {
+        let direction = self.target_position - self.position;
+        let direction = if direction.magnitude2() > 0.0 {
+            direction.normalize()
+        } else {
+            Vec2::unit_x()
+        };
+        let pos = terrain.ground_position(self.position);
+        let look_at = Self::look_at(terrain, self.position, direction);
+        actions.push(AntActionStruct::final_position(pos, look_at, self.index));
+    }
+
+    /// Steers away from the other ants nearby, stronger the closer they are
+    ///
+    /// Also returns whether another ant is already closer to the target, as the target can not be
+    /// shared once the ant is about to arrive.
+    fn separation(&mut self, world: &dyn WorldInterface) -> (Vec2, bool) {
+        let mut neighbours = std::mem::take(&mut self.neighbours);
+        neighbours.clear();
+        world.world_get_agents_in_radius(self.position, SEPARATION_RADIUS, &mut neighbours);
+
+        let distance_to_target = (self.target_position - self.position).magnitude2();
+        let mut push = Vec2::new(0.0, 0.0);
+        let mut is_target_taken = false;
+        for agent in &neighbours {
+            if agent.id == self.index || !agent.is_alive {
+                continue;
+            }
+
+            let offset = self.position - agent.position;
+            let distance = offset.magnitude();
+            let away = if distance > 0.0 {
+                offset / distance
+            } else if self.index < agent.id {
+                // ants on the same spot part in opposite directions
+                Vec2::unit_x()
+            } else {
+                -Vec2::unit_x()
+            };
+            push += away * (1.0 - distance / SEPARATION_RADIUS);
+
+            is_target_taken |=
+                (self.target_position - agent.position).magnitude2() < distance_to_target;
+        }
+
+        self.neighbours = neighbours;
+        (push, is_target_taken)
+    }
+
+    pub fn update(
+        &mut self,
+        tick: u64,
+        terrain: &HeightMapGenerator,
+        navigation: &mut NavigationGrid,
+        world: &dyn WorldInterface,
+        actions: &mut Vec<AntActionStruct>,
+    ) {
+        // place the ant on the first update
+        if !self.is_spawned {
+            let pos = terrain.ground_position(self.position);
+            let look_at = cgmath::Vector3::unit_x();
+            let unit_type = self.unit_type;
+            actions.push(AntActionStruct {
+                action: AntAction::Spawn(AntSpawnSnapshot {
+                    pos,
+                    look_at,
+                    unit_type,
+                }),
+                index: self.index,
+            });
+
+            self.is_spawned = true;
+            self.send_restored_state(actions);
+        }
+
+        if self.is_stopping {
+            self.is_stopping = false;
+            self.send_final_position(terrain, actions);
+        }
+
+        // a stun interrupts whatever the ant is doing
+        if self.status_effects.is_stunned() && self.state != State::Dead {
+            if self.state == State::Move {
+                self.path = None;
+                self.send_final_position(terrain, actions);
+            }
+            self.state = State::Idle;
+        }
+        self.ability_cooldown = self.ability_cooldown.saturating_sub(1);
+
         match self.state {
             // ##################################################
             State::Idle => {
                 // Set animation
-                if self.animation != AntAnimation::Idle {
-                    self.animation = AntAnimation::Idle;
-                    actions.push(AntActionStruct::from_animation(
-                        AntAnimation::Idle,
-                        self.index,
-                    ));
-                }
+                self.set_animation(AntAnimation::Idle, actions);
             }
             // ##################################################
             State::Move => {
-                let speed = 0.04;
-
                 // Check if position has been reached
                 if self.position == self.target_position {
                     self.state = State::Idle;
                     return;
                 }
 
+                // follow the shared flow field, a path is only planned where it ends
+                let flow_waypoint = match self.path {
+                    Some(_) => None,
+                    None => navigation.flow_waypoint(terrain, self.position, self.target_position),
+                };
+                let waypoint = match flow_waypoint {
+                    Some(waypoint) => waypoint,
+                    None => {
+                        let path = self.path.get_or_insert_with(|| {
+                            let mut path =
+                                navigation.find_path(terrain, self.position, self.target_position);
+                            path.reverse();
+                            path
+                        });
+                        let Some(&waypoint) = path.last() else {
+                            // the target can not be reached, the ant stays at the closest point
+                            self.state = State::Idle;
+                            return;
+                        };
+                        waypoint
+                    }
+                };
+
+                // slower uphill, faster downhill
+                let direction = (waypoint - self.position).normalize();
+                let rise = terrain.gradient(self.position).dot(direction);
+                let slope_factor =
+                    (1.0 - SLOPE_SLOWDOWN * rise).clamp(MIN_SLOPE_SPEED, MAX_SLOPE_SPEED);
+                let speed = self.speed * slope_factor * self.status_effects.speed_factor();
+
+                // keep some distance to the other ants
+                let (push, is_target_taken) = self.separation(world);
+                let mut velocity = direction + push * SEPARATION_WEIGHT;
+                if velocity.magnitude2() > 1.0 {
+                    velocity = velocity.normalize();
+                }
+
+                // another ant is closer to the target, once the ant is about to arrive or the
+                // others block its way this is as close as it gets
+                let is_close = (self.target_position - self.position).magnitude2()
+                    <= SEPARATION_RADIUS * SEPARATION_RADIUS;
+                let is_blocked = velocity.dot(direction) < MIN_PROGRESS;
+                if is_target_taken && (is_close || is_blocked) {
+                    self.path = None;
+                    self.state = State::Idle;
+                    self.send_final_position(terrain, actions);
+                    return;
+                }
+
                 // Set animation
                 if self.animation != AntAnimation::Walk {
                     self.animation = AntAnimation::Walk;
@@ -163,45 +565,123 @@ impl AntController {
                         index: self.index,
                     });
                     actions.push(AntActionStruct {
-                        action: AntAction::SetAnimationSpeed(speed),
+                        action: AntAction::SetAnimationSpeed(self.speed),
                         index: self.index,
                     });
                 }
 
                 // set position
-                let new_position =
-                    self.position + (self.target_position - self.position).normalize() * speed;
+                let new_position = self.position + velocity * speed;
 
-                // check if position has been reached
-                let finish_reached =
-                    (self.target_position - self.position).magnitude2() <= speed * speed;
+                // check if the waypoint has been reached
+                let waypoint_reached = (waypoint - self.position).magnitude2() <= speed * speed;
 
-                if finish_reached {
-                    self.position = self.target_position;
+                if waypoint_reached {
+                    self.position = waypoint;
+                    if let Some(path) = &mut self.path {
+                        path.pop();
+                    }
                 } else {
                     self.position = new_position;
                 }
+                let finish_reached = self.position == self.target_position
+                    || self.path.as_ref().is_some_and(|path| path.is_empty());
 
-                let pos = cgmath::Vector3 {
-                    x: self.position.x,
-                    y: self.position.y,
-                    z: 0.0,
-                };
-                let target =
-                    cgmath::Vector3::new(self.target_position.x, self.target_position.y, 0.0);
+                let pos = terrain.ground_position(self.position);
+                let look_at = Self::look_at(terrain, self.position, direction);
 
                 actions.push(if finish_reached {
-                    AntActionStruct::final_position(pos, target, *time_stamp, self.index)
+                    AntActionStruct::final_position(pos, look_at, self.index)
                 } else {
-                    AntActionStruct::update_position(pos, target, *time_stamp, self.index)
+                    AntActionStruct::update_position(pos, look_at, self.index)
+                });
+            }
+            // ##################################################
+            State::StartChargeShot => {
+                self.set_animation(AntAnimation::ChargeShot, actions);
+
+                self.start_charge_tick = tick;
+                self.state = State::ChargeShot;
+            }
+            // ##################################################
+            State::ChargeShot => {
+                let duration = tick - self.start_charge_tick;
+
+                if duration >= self.charge_duration {
+                    self.state = State::ShotCharged;
+                }
+            }
+            // ##################################################
+            State::ShotCharged => {
+                // wait for user action
+            }
+            // ##################################################
+            State::Shoot => {
+                // look at the target
+                let direction = self.shot_target_position - self.position;
+                if direction.magnitude2() > 0.0 {
+                    let pos = terrain.ground_position(self.position);
+                    let look_at = Self::look_at(terrain, self.position, direction.normalize());
+                    actions.push(AntActionStruct::final_position(pos, look_at, self.index));
+                }
+
+                self.shot = Some(Shot {
+                    owner_id: self.index,
+                    position: self.position,
+                    target_position: self.shot_target_position,
+                    damage: self.shot_damage,
+                    charge_duration: self.charge_duration,
+                    projectile: self.projectile,
                 });
+
+                self.state = State::Idle;
             }
             // ##################################################
-            State::ChargeShot => {}
+            State::StartCast => {
+                self.set_animation(AntAnimation::Cast, actions);
+
+                self.mana = 0.0;
+                self.start_cast_tick = tick;
+                self.state = State::Cast;
+            }
             // ##################################################
-            State::ShotCharged => {}
+            State::Cast => {
+                let Some(ability) = &self.ability else {
+                    self.state = State::Idle;
+                    return;
+                };
+
+                let duration = tick - self.start_cast_tick;
+                if duration < ability.cast_duration {
+                    return;
+                }
+
+                self.ability_cooldown = ability.cooldown;
+                self.state = State::Idle;
+
+                // a dash jumps straight towards the target, it stops in front of blocked terrain
+                let offset = self.cast_target_position - self.position;
+                if let AbilityKind::Dash { distance } = ability.kind
+                    && offset.magnitude2() > 0.0
+                {
+                    let direction = offset.normalize();
+                    let end = self.position + direction * offset.magnitude().min(distance);
+                    self.position = navigation.walkable_end(terrain, self.position, end);
+                    self.target_position = self.position;
+
+                    let pos = terrain.ground_position(self.position);
+                    let look_at = Self::look_at(terrain, self.position, direction);
+                    actions.push(AntActionStruct::final_position(pos, look_at, self.index));
+                }
+
+                self.cast = Some(Cast {
+                    owner_id: self.index,
+                    position: self.position,
+                    kind: ability.kind.clone(),
+                });
+            }
             // ##################################################
-            State::Shoot => {}
+            State::Dead => {}
         }
     }
 }
@@ -217,25 +697,77 @@ impl AntBodyInterface for AntController {
         self.position
     }
 
+    fn get_health_fraction(&self) -> f32 {
+        self.health / self.max_health
+    }
+
     fn move_to(&mut self, target_position: Vec2) {
-        self.target_position = target_position;
-        self.state = State::Move
+        if self.is_alive && !self.status_effects.is_stunned() && !self.is_casting() {
+            self.target_position = target_position;
+            self.path = None;
+            self.state = State::Move
+        }
     }
 
     fn is_moving(&self) -> bool {
         self.state == State::Move
     }
 
+    fn stop(&mut self) {
+        if self.state == State::Move {
+            self.path = None;
+            self.state = State::Idle;
+            self.is_stopping = true;
+        }
+    }
+
     fn charge_shot(&mut self) {
-        self.state = State::ChargeShot
+        if self.is_alive && !self.status_effects.is_stunned() && !self.is_casting() {
+            self.state = State::StartChargeShot
+        }
+    }
+
+    fn is_charging(&self) -> bool {
+        self.state == State::StartChargeShot || self.state == State::ChargeShot
     }
 
     fn is_shot_ready(&self) -> bool {
         self.state == State::ShotCharged
     }
 
-    fn shoot(&mut self) {
-        self.state = State::Shoot;
+    fn shoot(&mut self, target_position: Vec2) {
+        if self.state == State::ShotCharged {
+            self.shot_target_position = target_position;
+            self.state = State::Shoot;
+        }
+    }
+
+    fn is_ability_ready(&self) -> bool {
+        let is_mana_full = self
+            .ability
+            .as_ref()
+            .is_some_and(|ability| self.mana >= ability.mana_cost);
+
+        is_mana_full
+            && self.is_alive
+            && self.ability_cooldown == 0
+            && !self.status_effects.is_stunned()
+            && !self.is_casting()
+    }
+
+    fn cast_ability(&mut self, target_position: Vec2) {
+        if self.is_ability_ready() {
+            if self.state == State::Move {
+                self.path = None;
+                self.is_stopping = true;
+            }
+            self.cast_target_position = target_position;
+            self.state = State::StartCast;
+        }
+    }
+
+    fn is_casting(&self) -> bool {
+        self.state == State::StartCast || self.state == State::Cast
     }
 }
 
@@ -1,7 +1,7 @@
 //! Manages all ants instances
 //!
 
-use crate::ant_storage::Ant;
+use crate::{ant_storage::Ant, game_board::Faction, scenario::ARMY_DISTANCE};
 
 // use crate::game_logic::game_logic_interface::GameLogicMessageLight;
 
@@ -37,11 +37,19 @@ impl AntGenerator {
 
                 let color = color0 / 2.0 + color1 / 2.0;
 
+                // every second ant belongs to the opposing army
+                let (faction, offset) = if id % 2 == 0 {
+                    (Faction::Blue, 0.0)
+                } else {
+                    (Faction::Red, ARMY_DISTANCE)
+                };
+
                 ants.push(Ant {
                     id,
+                    faction,
 
                     pos: cgmath::Vector2 {
-                        x: x as f32 * 4.0,
+                        x: x as f32 * 4.0 + offset,
                         y: y as f32 * 4.0,
                     },
                     _rot_z: 0.0,
@@ -4,10 +4,23 @@
 // use market_economy_simulation_server::{ants, point_lights::Attenuation};
 
 // use crate::point_light_storage::{PointLightIndex, PointLightInterface, PointLightStorage};
-use forward_renderer::AnimatedObjectStorage;
+use cgmath::VectorSpace;
+use forward_renderer::{AnimatedObjectStorage, animation_shader};
+
+use crate::{
+    ant_controller::AntAnimation,
+    game_board::Faction,
+    resources,
+    status_effect::{StatusEffectKind, StatusEffectSet},
+    unit_definition::UnitDefinitions,
+};
+
+/// Time a dead ant takes to fade out after its death animation
+const DEATH_FADE_DURATION: instant::Duration = instant::Duration::from_millis(1500);
 
 pub struct Ant {
     pub id: usize,
+    pub faction: Faction,
 
     pub pos: cgmath::Vector2<f32>,
     pub _rot_z: f32,
@@ -16,35 +29,154 @@ pub struct Ant {
     pub _light_color: cgmath::Vector3<f32>,
 }
 
+/// Animation indices of a model, looked up by name from its table in the unit definitions
+struct ModelAnimations {
+    idle: usize,
+    walk: usize,
+    charge_shot: Option<usize>, // None keeps the current animation
+    death: Option<usize>,       // None fades out at once
+}
+
+/// How a unit type is drawn
+struct UnitTypeModel {
+    model: usize,
+    cast: Option<usize>, // None keeps the current animation
+}
+
 pub struct AntStorage {
     // pub point_light_storage: PointLightStorage,
-    pub animated_object_storage: AnimatedObjectStorage,
+    pub animated_object_storages: Vec<AnimatedObjectStorage>, // one for each model
+    models: Vec<ModelAnimations>,                             // one for each storage
+
+    unit_types: Vec<UnitTypeModel>,
+    ant_unit_types: Vec<usize>,
+    ant_instances: Vec<Option<usize>>, // instance in the storage of the model, None if not shown
+    ant_health: Vec<f32>,
+    ant_status_effects: Vec<StatusEffectSet>,
 
     max_ants: usize,
 }
 
 impl AntStorage {
     pub fn new(
-        // point_light_storage: PointLightStorage,
-        animated_object_storage: AnimatedObjectStorage,
+        wgpu_renderer: &mut dyn wgpu_renderer::wgpu_renderer::WgpuRendererInterface,
+        animation_bind_group_layout: &animation_shader::AnimationBindGroupLayout,
+        unit_definitions: &UnitDefinitions,
         max_ants: usize,
-    ) -> Self {
-        // assert_eq!(max_ants, point_light_storage.max_instances());
-        assert_eq!(max_ants, animated_object_storage.max_instances());
+    ) -> Result<Self, String> {
+        let mut model_names: Vec<&str> = Vec::new();
+        let mut animated_object_storages: Vec<AnimatedObjectStorage> = Vec::new();
+        let mut models = Vec::new();
+        let mut unit_types = Vec::new();
+
+        for unit in &unit_definitions.units {
+            // load every model only once
+            let model = match model_names.iter().position(|name| *name == unit.model) {
+                Some(model) => model,
+                None => {
+                    let name = unit.model.as_str();
+                    let glb_bin = resources::read(name)
+                        .map_err(|err| format!("unit '{}': {}", unit.name, err))?;
+
+                    let context = |err: String| format!("model '{}': {}", name, err);
+                    let storage = AnimatedObjectStorage::create_from_glb(
+                        wgpu_renderer,
+                        animation_bind_group_layout,
+                        &glb_bin,
+                        max_ants,
+                    )
+                    .map_err(context)?;
+
+                    // a clip missing in the glb is an error instead of playing another one
+                    let names = &unit_definitions.model(name)?.animations;
+                    let optional_index = |name: &Option<String>| {
+                        name.as_ref()
+                            .map(|name| storage.animation_index(name).map_err(context))
+                            .transpose()
+                    };
+                    models.push(ModelAnimations {
+                        idle: storage.animation_index(&names.idle).map_err(context)?,
+                        walk: storage.animation_index(&names.walk).map_err(context)?,
+                        charge_shot: optional_index(&names.charge_shot)?,
+                        death: optional_index(&names.death)?,
+                    });
+
+                    model_names.push(name);
+                    animated_object_storages.push(storage);
+                    animated_object_storages.len() - 1
+                }
+            };
+
+            let cast = unit
+                .ability
+                .as_ref()
+                .and_then(|ability| ability.animation.as_ref())
+                .map(|name| {
+                    animated_object_storages[model]
+                        .animation_index(name)
+                        .map_err(|err| {
+                            format!("unit '{}': model '{}': {}", unit.name, unit.model, err)
+                        })
+                })
+                .transpose()?;
+
+            unit_types.push(UnitTypeModel { model, cast });
+        }
+
+        let ant_unit_types = vec![0; max_ants];
+        let ant_instances = vec![None; max_ants];
+        let ant_health = vec![1.0; max_ants];
+        let ant_status_effects = vec![StatusEffectSet::default(); max_ants];
 
-        Self {
+        Ok(Self {
             // point_light_storage,
-            animated_object_storage,
+            animated_object_storages,
+            models,
+            unit_types,
+            ant_unit_types,
+            ant_instances,
+            ant_health,
+            ant_status_effects,
             max_ants,
+        })
+    }
+
+    /// The storage of the model of the ant and the instance of the ant in it
+    fn instance(&mut self, index: usize) -> Option<(&mut AnimatedObjectStorage, usize)> {
+        let instance = self.ant_instances[index]?;
+        let model = self.unit_types[self.ant_unit_types[index]].model;
+
+        Some((&mut self.animated_object_storages[model], instance))
+    }
+
+    /// Takes a free instance of the model of the ant
+    fn show(&mut self, index: usize) {
+        let model = self.unit_types[self.ant_unit_types[index]].model;
+        self.ant_instances[index] = self.animated_object_storages[model].spawn();
+
+        if self.ant_instances[index].is_none() {
+            log::warn!("no free instance of model {} for ant {}", model, index);
+        }
+    }
+
+    /// Frees the instance of the ant at once
+    fn hide(&mut self, index: usize) {
+        if let Some((storage, instance)) = self.instance(index) {
+            storage.set_inactive(instance);
         }
+        self.ant_instances[index] = None;
     }
 
     pub fn set_ant(&mut self, ant: &Ant) {
         if ant.id < self.max_ants {
-            let pos = cgmath::Vector3::new(ant.pos.x + 0.0, ant.pos.y + 15.0, 0.00);
-            // let pos = cgmath::Vector3::new(0.0, 0.0, 0.0);
-            self.animated_object_storage
-                .set_pos(ant.id, pos, cgmath::Vector3::unit_x());
+            // placeholder until the worker spawns the ant on the terrain
+            self.hide(ant.id);
+            self.show(ant.id);
+
+            let pos = ant.pos.extend(0.0);
+            if let Some((storage, instance)) = self.instance(ant.id) {
+                storage.set_pos(instance, pos, cgmath::Vector3::unit_x());
+            }
             // self.point_light_storage.set_position(&PointLightIndex{ instance_index: ant.id }, pos);
             // self.point_light_storage.set_light(
             //     PointLightIndex {
@@ -55,7 +187,6 @@ impl AntStorage {
             //     Attenuation::_200,
             // );
 
-            self.animated_object_storage.set_active(ant.id);
             // self.point_light_storage.set_active(
             //     &PointLightIndex {
             //         instance_index: ant.id,
@@ -71,16 +202,130 @@ impl AntStorage {
         pos: cgmath::Vector3<f32>,
         look_at: cgmath::Vector3<f32>,
     ) {
-        self.animated_object_storage.set_pos(index, pos, look_at);
+        if let Some((storage, instance)) = self.instance(index) {
+            storage.set_pos(instance, pos, look_at);
+        }
     }
 
-    pub fn set_animation(&mut self, index: usize, animation_index: usize) {
-        self.animated_object_storage
-            .set_animation(index, animation_index);
+    pub fn set_animation(&mut self, index: usize, animation: AntAnimation) {
+        let unit_type = &self.unit_types[self.ant_unit_types[index]];
+        let model = &self.models[unit_type.model];
+        let animation_index = match animation {
+            AntAnimation::Idle => Some(model.idle),
+            AntAnimation::Walk => Some(model.walk),
+            AntAnimation::ChargeShot => model.charge_shot,
+            AntAnimation::Cast => unit_type.cast,
+        };
+
+        // without a clip for the state the current one keeps playing
+        let Some(animation_index) = animation_index else {
+            return;
+        };
+
+        if let Some((storage, instance)) = self.instance(index) {
+            storage.set_animation(instance, animation_index);
+        }
     }
 
     pub fn set_animation_speed(&mut self, index: usize, speed: f32) {
-        self.animated_object_storage
-            .set_animation_speed(index, speed * 60.0);
+        if let Some((storage, instance)) = self.instance(index) {
+            storage.set_animation_speed(instance, speed * 60.0);
+        }
+    }
+
+    /// Tints the ant from its base color to red, health is between 0.0 and 1.0
+    pub fn set_health(&mut self, index: usize, health: f32) {
+        self.ant_health[index] = health;
+        self.update_color(index);
+    }
+
+    /// Tints the ant in the color of its most important status effect
+    pub fn set_status_effects(&mut self, index: usize, status_effects: StatusEffectSet) {
+        self.ant_status_effects[index] = status_effects;
+        self.update_color(index);
+    }
+
+    fn update_color(&mut self, index: usize) {
+        let healthy = cgmath::Vector3::new(0.5, 0.5, 0.8);
+        let hurt = cgmath::Vector3::new(0.8, 0.1, 0.1);
+        let mut color = hurt.lerp(healthy, self.ant_health[index].clamp(0.0, 1.0));
+
+        // ordered by importance
+        let effect_colors = [
+            (StatusEffectKind::Stun, cgmath::Vector3::new(1.0, 1.0, 0.2)),
+            (StatusEffectKind::Burn, cgmath::Vector3::new(1.0, 0.5, 0.0)),
+            (
+                StatusEffectKind::Shield,
+                cgmath::Vector3::new(0.2, 0.9, 1.0),
+            ),
+            (StatusEffectKind::Slow, cgmath::Vector3::new(0.1, 0.2, 1.0)),
+            (
+                StatusEffectKind::ArmorShred,
+                cgmath::Vector3::new(0.6, 0.2, 0.6),
+            ),
+            (StatusEffectKind::Haste, cgmath::Vector3::new(0.2, 1.0, 0.2)),
+        ];
+        if let Some((_, effect_color)) = effect_colors
+            .iter()
+            .find(|(kind, _)| self.ant_status_effects[index].contains(*kind))
+        {
+            color = color.lerp(*effect_color, 0.5);
+        }
+
+        if let Some((storage, instance)) = self.instance(index) {
+            storage.set_color(instance, [color.x, color.y, color.z, 1.0]);
+        }
+    }
+
+    /// Shows a fresh ant of the unit type in its idle animation
+    pub fn spawn(&mut self, index: usize, unit_type: usize) {
+        // the ant takes a new instance in the storage of its model
+        self.hide(index);
+        self.ant_unit_types[index] = unit_type;
+        self.show(index);
+
+        self.set_animation(index, AntAnimation::Idle);
+        self.ant_status_effects[index] = StatusEffectSet::default();
+        self.set_health(index, 1.0);
+    }
+
+    /// Hides all ants until they are spawned again, also the ones still fading out
+    pub fn despawn_all(&mut self) {
+        self.ant_instances.fill(None);
+        for storage in &mut self.animated_object_storages {
+            for instance in 0..storage.max_instances() {
+                storage.set_inactive(instance);
+            }
+        }
+    }
+
+    /// Darkens the ant and plays its death animation if its model has one, the instance is freed
+    /// once it has faded out
+    pub fn set_dead(&mut self, index: usize) {
+        let death = self.models[self.unit_types[self.ant_unit_types[index]].model].death;
+        if let Some((storage, instance)) = self.instance(index) {
+            storage.set_color(instance, [0.1, 0.1, 0.1, 1.0]);
+            storage.despawn(instance, death, DEATH_FADE_DURATION);
+        }
+        self.ant_instances[index] = None;
+    }
+
+    pub fn update(
+        &mut self,
+        wgpu_renderer: &mut dyn wgpu_renderer::wgpu_renderer::WgpuRendererInterface,
+        dt: &instant::Duration,
+    ) {
+        for storage in &mut self.animated_object_storages {
+            storage.update_animations(dt);
+            storage.update_device_data(wgpu_renderer);
+        }
+    }
+}
+
+#[test]
+fn test_models_of_the_unit_definitions_are_loaded_from_res() {
+    let unit_definitions = UnitDefinitions::load_default().unwrap();
+    for name in unit_definitions.models.keys() {
+        assert!(!resources::read(name).unwrap().is_empty());
     }
 }
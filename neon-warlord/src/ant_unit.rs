@@ -0,0 +1,52 @@
+//! Bundles the controller and the ai of a single ant
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    ant_ai::AntAi, ant_controller::AntController, game_board::Faction,
+    unit_definition::UnitDefinitions,
+};
+
+type Vec2 = cgmath::Vector2<f32>;
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct AntUnit {
+    pub id: usize,
+    pub unit_type: String,
+    pub faction: Faction,
+    pub controller: AntController,
+    pub ai: AntAi,
+    pub last_attacker_id: Option<usize>,
+
+    // Statistics
+    pub damage_dealt: f32,
+    pub damage_received: f32,
+}
+
+impl AntUnit {
+    pub fn new(
+        id: usize,
+        unit_type: &str,
+        position: Vec2,
+        faction: Faction,
+        unit_definitions: &UnitDefinitions,
+    ) -> Result<Self, String> {
+        let unit_type_index = unit_definitions.index_of(unit_type)?;
+        let definition = &unit_definitions.units[unit_type_index];
+
+        let controller = AntController::new(position, id, unit_type_index, definition);
+        let brain = unit_definitions.brain(&definition.brain)?;
+        let ai = AntAi::new(id, faction, definition, brain);
+
+        Ok(Self {
+            id,
+            unit_type: unit_type.to_string(),
+            faction,
+            controller,
+            ai,
+            last_attacker_id: None,
+            damage_dealt: 0.0,
+            damage_received: 0.0,
+        })
+    }
+}
@@ -0,0 +1,351 @@
+//! Runs many seeded battles between two armies and aggregates their outcome
+
+use std::{fmt, thread};
+
+use serde::Serialize;
+
+use crate::{
+    battle_result::BattleResult,
+    game_board::Faction,
+    headless::HeadlessBattle,
+    scenario::{Army, Scenario},
+    unit_definition::UnitDefinitions,
+};
+
+/// z-value of the 95% confidence intervals
+const Z_95: f64 = 1.96;
+
+pub struct BalanceSettings {
+    pub nr_battles: usize,
+    pub nr_threads: usize,
+    pub seed: u64, // seed of the first battle, the following battles count up
+    pub max_ticks: u64,
+    pub max_nr_orbs: usize,
+}
+
+#[derive(Serialize)]
+pub struct Statistic {
+    pub metric: String,
+    pub army: String,
+    pub unit_type: String,
+    pub samples: usize,
+    pub mean: f64,
+    pub ci_low: f64,
+    pub ci_high: f64,
+}
+
+#[derive(Serialize)]
+pub struct BalanceReport {
+    pub nr_battles: usize,
+    pub seed: u64,
+    pub statistics: Vec<Statistic>,
+}
+
+pub fn run_balance(
+    armies: &[Army; 2],
+    unit_definitions: &UnitDefinitions,
+    settings: &BalanceSettings,
+) -> BalanceReport {
+    let seeds: Vec<u64> = (0..settings.nr_battles as u64)
+        .map(|i| settings.seed.wrapping_add(i))
+        .collect();
+    let chunk_size = seeds.len().div_ceil(settings.nr_threads.max(1)).max(1);
+
+    // the results keep the order of the seeds, independent of the number of threads
+    let results: Vec<BattleResult> = thread::scope(|scope| {
+        let handles: Vec<_> = seeds
+            .chunks(chunk_size)
+            .map(|seeds| {
+                scope.spawn(move || {
+                    seeds
+                        .iter()
+                        .map(|seed| run_battle(armies, unit_definitions, *seed, settings))
+                        .collect::<Vec<_>>()
+                })
+            })
+            .collect();
+
+        handles
+            .into_iter()
+            .flat_map(|handle| handle.join().expect("battle thread panicked"))
+            .collect()
+    });
+
+    BalanceReport {
+        nr_battles: settings.nr_battles,
+        seed: settings.seed,
+        statistics: aggregate(armies, &results),
+    }
+}
+
+fn run_battle(
+    armies: &[Army; 2],
+    unit_definitions: &UnitDefinitions,
+    seed: u64,
+    settings: &BalanceSettings,
+) -> BattleResult {
+    let scenario = Scenario::from_armies(armies, seed);
+
+    HeadlessBattle::new(scenario, unit_definitions.clone(), settings.max_nr_orbs)
+        .run(settings.max_ticks)
+}
+
+fn aggregate(armies: &[Army; 2], results: &[BattleResult]) -> Vec<Statistic> {
+    let mut statistics = Vec::new();
+    let nr_battles = results.len();
+
+    // outcome
+    for army in armies {
+        let wins = results
+            .iter()
+            .filter(|result| result.is_finished && result.winner == Some(army.faction))
+            .count();
+        statistics.push(Statistic::rate(
+            "win_rate",
+            Some(army.faction),
+            "",
+            wins,
+            nr_battles,
+        ));
+    }
+
+    let draws = results
+        .iter()
+        .filter(|result| result.is_finished && result.winner.is_none())
+        .count();
+    statistics.push(Statistic::rate("draw_rate", None, "", draws, nr_battles));
+
+    let timeouts = results.iter().filter(|result| !result.is_finished).count();
+    statistics.push(Statistic::rate(
+        "timeout_rate",
+        None,
+        "",
+        timeouts,
+        nr_battles,
+    ));
+
+    let ticks: Vec<f64> = results.iter().map(|result| result.ticks as f64).collect();
+    statistics.push(Statistic::mean("battle_length", None, "", &ticks));
+
+    // units
+    for army in armies {
+        // an army may list a unit type more than once, its units are pooled into one row
+        for unit_type in army.unit_types() {
+            let units: Vec<_> = results
+                .iter()
+                .flat_map(|result| &result.units)
+                .filter(|unit| unit.faction == army.faction && unit.unit_type == unit_type)
+                .collect();
+
+            let dealt: Vec<f64> = units.iter().map(|unit| unit.damage_dealt as f64).collect();
+            statistics.push(Statistic::mean(
+                "damage_dealt",
+                Some(army.faction),
+                unit_type,
+                &dealt,
+            ));
+
+            let received: Vec<f64> = units
+                .iter()
+                .map(|unit| unit.damage_received as f64)
+                .collect();
+            statistics.push(Statistic::mean(
+                "damage_received",
+                Some(army.faction),
+                unit_type,
+                &received,
+            ));
+
+            let survivors = units.iter().filter(|unit| unit.is_alive).count();
+            statistics.push(Statistic::rate(
+                "survival_rate",
+                Some(army.faction),
+                unit_type,
+                survivors,
+                units.len(),
+            ));
+        }
+    }
+
+    statistics
+}
+
+impl Statistic {
+    /// Mean with a normal approximated confidence interval
+    fn mean(metric: &str, army: Option<Faction>, unit_type: &str, samples: &[f64]) -> Self {
+        let n = samples.len();
+        let mean = samples.iter().sum::<f64>() / n.max(1) as f64;
+
+        let variance = if n > 1 {
+            samples.iter().map(|x| (x - mean) * (x - mean)).sum::<f64>() / (n - 1) as f64
+        } else {
+            0.0
+        };
+        let margin = Z_95 * (variance / n.max(1) as f64).sqrt();
+
+        Self::new(
+            metric,
+            army,
+            unit_type,
+            n,
+            mean,
+            mean - margin,
+            mean + margin,
+        )
+    }
+
+    /// Rate with a Wilson score interval, which behaves well for rates close to 0 or 1
+    fn rate(
+        metric: &str,
+        army: Option<Faction>,
+        unit_type: &str,
+        successes: usize,
+        n: usize,
+    ) -> Self {
+        if n == 0 {
+            return Self::new(metric, army, unit_type, 0, 0.0, 0.0, 0.0);
+        }
+
+        let n_f = n as f64;
+        let p = successes as f64 / n_f;
+        let z2 = Z_95 * Z_95;
+
+        let denominator = 1.0 + z2 / n_f;
+        let center = (p + z2 / (2.0 * n_f)) / denominator;
+        let margin = Z_95 * (p * (1.0 - p) / n_f + z2 / (4.0 * n_f * n_f)).sqrt() / denominator;
+
+        let ci_low = (center - margin).max(0.0);
+        let ci_high = (center + margin).min(1.0);
+
+        Self::new(metric, army, unit_type, n, p, ci_low, ci_high)
+    }
+
+    fn new(
+        metric: &str,
+        army: Option<Faction>,
+        unit_type: &str,
+        samples: usize,
+        mean: f64,
+        ci_low: f64,
+        ci_high: f64,
+    ) -> Self {
+        let army = match army {
+            Some(faction) => format!("{:?}", faction),
+            None => String::new(),
+        };
+
+        Self {
+            metric: metric.to_string(),
+            army,
+            unit_type: unit_type.to_string(),
+            samples,
+            mean,
+            ci_low,
+            ci_high,
+        }
+    }
+}
+
+impl BalanceReport {
+    pub fn to_csv(&self) -> String {
+        let mut csv = String::from("metric,army,unit_type,samples,mean,ci_low,ci_high\n");
+
+        for elem in &self.statistics {
+            csv += &format!(
+                "{},{},{},{},{},{},{}\n",
+                elem.metric,
+                elem.army,
+                elem.unit_type,
+                elem.samples,
+                elem.mean,
+                elem.ci_low,
+                elem.ci_high
+            );
+        }
+
+        csv
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).expect("balance report is always serializable")
+    }
+}
+
+impl fmt::Display for BalanceReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Battles: {} (seed {})", self.nr_battles, self.seed)?;
+        writeln!(f)?;
+        writeln!(
+            f,
+            "{:<16} {:>6} {:>12} {:>8} {:>10} {:>22}",
+            "metric", "army", "type", "samples", "mean", "95% ci"
+        )?;
+
+        for elem in &self.statistics {
+            writeln!(
+                f,
+                "{:<16} {:>6} {:>12} {:>8} {:>10.3} {:>10.3} - {:<10.3}",
+                elem.metric,
+                elem.army,
+                elem.unit_type,
+                elem.samples,
+                elem.mean,
+                elem.ci_low,
+                elem.ci_high
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+#[test]
+fn test_statistics() {
+    use crate::battle_result::UnitResult;
+
+    let close = |a: f64, b: f64| (a - b).abs() < 1e-4;
+
+    // Wilson score interval of 5 successes in 10 samples
+    let rate = Statistic::rate("rate", None, "", 5, 10);
+    assert!(close(rate.mean, 0.5) && close(rate.ci_low, 0.2366) && close(rate.ci_high, 0.7634));
+
+    // all successes keep the interval inside of [0, 1]
+    let rate = Statistic::rate("rate", None, "", 10, 10);
+    assert!(close(rate.mean, 1.0) && close(rate.ci_low, 0.7225) && close(rate.ci_high, 1.0));
+
+    // sample variance 2.5, margin 1.96 * sqrt(2.5 / 5)
+    let mean = Statistic::mean("mean", None, "", &[1.0, 2.0, 3.0, 4.0, 5.0]);
+    assert!(close(mean.mean, 3.0) && close(mean.ci_low, 1.6141) && close(mean.ci_high, 4.3859));
+
+    // a repeated unit type gets one row over all of its units
+    let armies = [
+        Army::parse(Faction::Blue, "ant:2,ant:3").unwrap(),
+        Army::parse(Faction::Red, "heavy_ant:1").unwrap(),
+    ];
+    let unit = |id, unit_type: &str, faction| UnitResult {
+        id,
+        unit_type: unit_type.to_string(),
+        faction,
+        is_alive: true,
+        health: 100.0,
+        damage_dealt: 0.0,
+        damage_received: 0.0,
+    };
+    let results = [BattleResult {
+        is_finished: true,
+        winner: Some(Faction::Blue),
+        ticks: 100,
+        units: (0..5)
+            .map(|id| unit(id, "ant", Faction::Blue))
+            .chain([unit(5, "heavy_ant", Faction::Red)])
+            .collect(),
+    }];
+
+    let statistics = aggregate(&armies, &results);
+    let survival: Vec<_> = statistics
+        .iter()
+        .filter(|elem| elem.metric == "survival_rate" && elem.army == "Blue")
+        .collect();
+    assert_eq!(survival.len(), 1);
+    assert_eq!(survival[0].samples, 5);
+}
@@ -0,0 +1,137 @@
+//! Events of the battle reported by the worker, for kill feeds, statistics and analytics
+
+use serde::Serialize;
+
+use crate::{game_board::Faction, status_effect::StatusEffectKind};
+
+type Vec2 = cgmath::Vector2<f32>;
+
+#[derive(Clone, Copy, Debug, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum BattleEventKind {
+    UnitSpawned {
+        unit_id: usize,
+        faction: Faction,
+        position: Vec2,
+    },
+    TargetAcquired {
+        unit_id: usize,
+        target_id: usize,
+    },
+    ShotFired {
+        unit_id: usize,
+        target_position: Vec2,
+    },
+    Hit {
+        source_id: usize,
+        target_id: usize,
+        damage: f32, // damage of the orb or the burn, before it is applied
+    },
+    Damage {
+        source_id: usize,
+        target_id: usize,
+        damage: f32, // damage which has actually been applied
+        health: f32, // remaining health of the target
+    },
+    AbilityCast {
+        unit_id: usize,
+        ability: &'static str,
+    },
+    StatusEffectApplied {
+        source_id: usize,
+        target_id: usize,
+        kind: StatusEffectKind,
+    },
+    Death {
+        unit_id: usize,
+        killer_id: usize,
+    },
+    RoundEnded {
+        round: u32,
+        winner: Option<Faction>, // None if the battle is a draw or ran out of time
+    },
+}
+
+#[derive(Clone, Copy, Debug, Serialize)]
+pub struct BattleEvent {
+    pub tick: u64,
+    #[serde(flatten)]
+    pub kind: BattleEventKind,
+}
+
+/// All events of a match, in the order they happened
+#[derive(Default)]
+pub struct BattleLog {
+    events: Vec<BattleEvent>,
+}
+
+impl BattleLog {
+    pub fn new() -> Self {
+        let events = Vec::new();
+
+        Self { events }
+    }
+
+    pub fn extend(&mut self, events: &[BattleEvent]) {
+        self.events.extend_from_slice(events);
+    }
+
+    /// One json object per line
+    pub fn to_json_lines(&self) -> String {
+        let mut lines = String::new();
+
+        for event in &self.events {
+            let line = serde_json::to_string(event).expect("battle event is always serializable");
+            lines.push_str(&line);
+            lines.push('\n');
+        }
+
+        lines
+    }
+
+    /// Files are only available on desktop, in the browser this returns an error
+    pub fn write_to_file(&self, path: &str) -> Result<(), String> {
+        std::fs::write(path, self.to_json_lines()).map_err(|err| format!("{}: {}", path, err))
+    }
+}
+
+#[test]
+fn test_battle_log_matches_the_result() {
+    use crate::{
+        ant_generator::AntGenerator, headless::HeadlessBattle, scenario::Scenario,
+        unit_definition::UnitDefinitions,
+    };
+
+    let ant_generator = AntGenerator::new(16);
+    let scenario = Scenario::from_ant_generator(&ant_generator);
+    let unit_definitions = UnitDefinitions::load_default().unwrap();
+
+    let mut battle = HeadlessBattle::new(scenario, unit_definitions, 16);
+    let result = battle.run(20_000);
+    let json_lines = battle.battle_log().to_json_lines();
+
+    let count = |event_type: &str| {
+        let pattern = format!("\"type\":\"{}\"", event_type);
+        json_lines
+            .lines()
+            .filter(|line| line.contains(&pattern))
+            .count()
+    };
+    assert_eq!(count("unit_spawned"), result.units.len());
+    assert_eq!(
+        count("death"),
+        result.units.iter().filter(|unit| !unit.is_alive).count()
+    );
+    assert!(count("shot_fired") >= count("death"));
+    assert_eq!(count("round_ended"), 1);
+
+    // only the ants still alive are hit
+    let mut is_dead = vec![false; result.units.len()];
+    for event in &battle.battle_log().events {
+        match event.kind {
+            BattleEventKind::Hit { target_id, .. } => assert!(!is_dead[target_id]),
+            BattleEventKind::Death { unit_id, .. } => is_dead[unit_id] = true,
+            _ => (),
+        }
+    }
+}
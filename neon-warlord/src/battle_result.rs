@@ -0,0 +1,64 @@
+//! Summary of a battle, as reported by the worker
+
+use std::fmt;
+
+use crate::game_board::Faction;
+
+pub struct UnitResult {
+    pub id: usize,
+    pub unit_type: String,
+    pub faction: Faction,
+    pub is_alive: bool,
+    pub health: f32,
+    pub damage_dealt: f32,
+    pub damage_received: f32,
+}
+
+pub struct BattleResult {
+    pub is_finished: bool,
+    pub winner: Option<Faction>, // None if the battle is a draw or not finished
+    pub ticks: u64,
+    pub units: Vec<UnitResult>,
+}
+
+impl BattleResult {
+    pub fn survivors(&self) -> impl Iterator<Item = &UnitResult> {
+        self.units.iter().filter(|unit| unit.is_alive)
+    }
+}
+
+impl fmt::Display for BattleResult {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let winner = match (self.is_finished, self.winner) {
+            (false, _) => "none (time limit reached)".to_string(),
+            (true, None) => "none (draw)".to_string(),
+            (true, Some(faction)) => format!("{:?}", faction),
+        };
+
+        writeln!(f, "Winner:    {}", winner)?;
+        writeln!(f, "Ticks:     {}", self.ticks)?;
+        writeln!(f, "Survivors: {}", self.survivors().count())?;
+        writeln!(f)?;
+        writeln!(
+            f,
+            "{:>4} {:>12} {:>8} {:>6} {:>8} {:>8} {:>8}",
+            "id", "type", "faction", "alive", "health", "dealt", "received"
+        )?;
+
+        for unit in &self.units {
+            writeln!(
+                f,
+                "{:>4} {:>12} {:>8} {:>6} {:>8.1} {:>8.1} {:>8.1}",
+                unit.id,
+                unit.unit_type,
+                format!("{:?}", unit.faction),
+                unit.is_alive,
+                unit.health,
+                unit.damage_dealt,
+                unit.damage_received
+            )?;
+        }
+
+        Ok(())
+    }
+}
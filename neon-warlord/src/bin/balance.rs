@@ -0,0 +1,48 @@
+//! Runs many seeded battles between two armies and reports win rates and damage per unit type
+//!
+//! Usage: balance <blue_army> <red_army> [nr_battles] [nr_threads] [seed] [output.csv|output.json]
+//!
+//! Example: balance ant:16 ant:12 1000 8 0 report.csv
+
+const USAGE: &str = "Usage: balance <blue_army> <red_army> [nr_battles] [nr_threads] [seed] [output.csv|output.json]";
+
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+
+    if args.len() < 2 {
+        eprintln!("{}", USAGE);
+        std::process::exit(1);
+    }
+
+    let nr_battles = parse_arg(args.get(2), 100, "nr_battles");
+    let nr_threads = parse_arg(
+        args.get(3),
+        std::thread::available_parallelism().map_or(1, |n| n.get()),
+        "nr_threads",
+    );
+    let seed = parse_arg(args.get(4), 0, "seed");
+    let output = args.get(5).map(|arg| arg.as_str());
+
+    if let Err(err) =
+        neon_warlord::run_balance(&args[0], &args[1], nr_battles, nr_threads, seed, output)
+    {
+        eprintln!("{}", err);
+        std::process::exit(1);
+    }
+}
+
+/// The argument if given, prints the usage and exits if it is not a number
+fn parse_arg<T: std::str::FromStr>(arg: Option<&String>, default: T, name: &str) -> T {
+    let Some(arg) = arg else {
+        return default;
+    };
+
+    match arg.parse() {
+        Ok(value) => value,
+        Err(_) => {
+            eprintln!("invalid {} '{}'", name, arg);
+            eprintln!("{}", USAGE);
+            std::process::exit(1);
+        }
+    }
+}
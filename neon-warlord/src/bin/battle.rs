@@ -0,0 +1,34 @@
+//! Runs a battle without a window and prints the outcome
+//!
+//! Usage: battle [nr_ants] [max_ticks] [battle_log.jsonl]
+
+const USAGE: &str = "Usage: battle [nr_ants] [max_ticks] [battle_log.jsonl]";
+
+fn main() {
+    let mut args = std::env::args().skip(1);
+
+    let nr_ants = parse_arg(args.next(), 64, "nr_ants");
+    let max_ticks = parse_arg(args.next(), 60 * 60 * 10, "max_ticks");
+    let battle_log_path = args.next();
+
+    if let Err(err) = neon_warlord::run_headless(nr_ants, max_ticks, battle_log_path.as_deref()) {
+        eprintln!("{}", err);
+        std::process::exit(1);
+    }
+}
+
+/// The argument if given, prints the usage and exits if it is not a number
+fn parse_arg<T: std::str::FromStr>(arg: Option<String>, default: T, name: &str) -> T {
+    let Some(arg) = arg else {
+        return default;
+    };
+
+    match arg.parse() {
+        Ok(value) => value,
+        Err(_) => {
+            eprintln!("invalid {} '{}'", name, arg);
+            eprintln!("{}", USAGE);
+            std::process::exit(1);
+        }
+    }
+}
@@ -0,0 +1,152 @@
+//! Moves the orbs fired by the ants and resolves their explosions against the agents of the game board
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    ant_ai::WorldInterface,
+    game_board::Faction,
+    heightmap_generator::HeightMapGenerator,
+    orb_controller::{OrbActionStruct, OrbController, OrbInterface},
+    status_effect::StatusEffectDefinition,
+    unit_definition::{ProjectileDefinition, ProjectileType},
+};
+
+type Vec2 = cgmath::Vector2<f32>;
+type Vec3 = cgmath::Vector3<f32>;
+
+/// Height of the orbs above the terrain
+const ORB_HEIGHT: f32 = 1.5;
+
+/// A shot fired by an ant, waiting to be spawned as orb
+#[derive(Clone, Copy, Serialize, Deserialize)]
+pub struct Shot {
+    pub owner_id: usize,
+    pub position: Vec2,
+    pub target_position: Vec2,
+    pub damage: f32,
+    pub charge_duration: u64, // ticks the orb grows before it launches
+    pub projectile: ProjectileDefinition,
+}
+
+/// Damage dealt by an orb or a burn to an agent
+#[derive(Clone, Copy)]
+pub struct Hit {
+    pub source_id: usize,
+    pub target_id: usize,
+    pub damage: f32,
+    pub effect: Option<StatusEffectDefinition>,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+struct Projectile {
+    orb: OrbController,
+
+    owner_id: usize,
+    faction: Faction,
+    target_position: Vec3,
+    damage: f32,
+    explosion_radius: f32,
+    effect: Option<StatusEffectDefinition>,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Combat {
+    projectiles: Vec<Projectile>,
+}
+
+impl Combat {
+    pub fn new(max_nr_orbs: usize) -> Self {
+        let mut projectiles = Vec::with_capacity(max_nr_orbs);
+        for i in 0..max_nr_orbs {
+            let position = Vec3::new(0.0, 0.0, 0.0);
+
+            projectiles.push(Projectile {
+                orb: OrbController::new(position, i),
+                owner_id: 0,
+                faction: Faction::Blue,
+                target_position: position,
+                damage: 0.0,
+                explosion_radius: 0.0,
+                effect: None,
+            });
+        }
+
+        Self { projectiles }
+    }
+
+    pub fn nr_orbs(&self) -> usize {
+        self.projectiles.len()
+    }
+
+    /// Charges a free orb at the position of the shot, the shot is dropped if all orbs are in use
+    pub fn spawn(&mut self, shot: Shot, faction: Faction, terrain: &HeightMapGenerator) -> bool {
+        match shot.projectile.projectile_type {
+            ProjectileType::PlasmaOrb => {
+                // the only projectile so far, all of the pool are plasma orbs
+            }
+        }
+
+        let Some(projectile) = self.projectiles.iter_mut().find(|elem| elem.orb.is_idle()) else {
+            log::warn!("no free orb for the shot of ant {}", shot.owner_id);
+            return false;
+        };
+
+        projectile.owner_id = shot.owner_id;
+        projectile.faction = faction;
+        projectile.target_position =
+            terrain.ground_position(shot.target_position) + Vec3::new(0.0, 0.0, ORB_HEIGHT);
+        projectile.damage = shot.damage;
+        projectile.explosion_radius = shot.projectile.explosion_radius;
+        projectile.effect = shot.projectile.effect;
+        projectile
+            .orb
+            .set_projectile(&shot.projectile, shot.charge_duration);
+
+        projectile
+            .orb
+            .set_position(terrain.ground_position(shot.position) + Vec3::new(0.0, 0.0, ORB_HEIGHT));
+        projectile.orb.start_charge();
+
+        true
+    }
+
+    /// Advances all orbs and collects the hits of the exploding ones
+    pub fn update(
+        &mut self,
+        tick: u64,
+        world: &dyn WorldInterface,
+        hits: &mut Vec<Hit>,
+        actions: &mut Vec<OrbActionStruct>,
+    ) {
+        let mut agents = Vec::new();
+
+        for projectile in &mut self.projectiles {
+            let orb = &mut projectile.orb;
+
+            // launch charged orbs
+            if orb.is_charged() {
+                orb.move_to(projectile.target_position);
+            }
+
+            // area damage
+            if orb.is_exploding() {
+                let center = orb.get_position().truncate();
+
+                agents.clear();
+                world.world_get_agents_in_radius(center, projectile.explosion_radius, &mut agents);
+                for agent in &agents {
+                    if agent.is_alive && agent.faction != projectile.faction {
+                        hits.push(Hit {
+                            source_id: projectile.owner_id,
+                            target_id: agent.id,
+                            damage: projectile.damage,
+                            effect: projectile.effect,
+                        });
+                    }
+                }
+            }
+
+            orb.update(tick, actions);
+        }
+    }
+}
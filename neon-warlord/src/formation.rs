@@ -0,0 +1,223 @@
+//! Slots the army of one faction is placed in before a battle
+//!
+//! The slots form a grid behind the front line of the faction, row 0 is the front. A formation
+//! orders the slots by priority and the units fill them in that order.
+
+use cgmath::InnerSpace;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    game_board::Faction,
+    scenario::{ARMY_DISTANCE, UNIT_SPACING},
+};
+
+type Vec2 = cgmath::Vector2<f32>;
+
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Formation {
+    Line,   // as wide as possible, the front row first
+    Wedge,  // a triangle with its tip towards the enemy
+    Box,    // a square block
+    Spread, // a line with an empty slot around every unit
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct FormationGrid {
+    front_line: f32,
+    direction: f32, // the rows continue along x in this direction
+    columns: usize,
+
+    formation: Formation,      // newly placed units take its next free slot
+    slots: Vec<Option<usize>>, // id of the unit in each slot, row by row from the front
+}
+
+impl FormationGrid {
+    /// The grid has room for all the units, even when they are spread
+    pub fn new(faction: Faction, max_nr_units: usize) -> Self {
+        // the armies face each other like in the scenarios
+        let (front_line, direction) = match faction {
+            Faction::Blue => (0.0, -1.0),
+            Faction::Red => (ARMY_DISTANCE, 1.0),
+        };
+
+        let mut side = max_nr_units.isqrt().max(1);
+        if side * side < max_nr_units {
+            side += 1;
+        }
+        let columns = 2 * side;
+        let rows = 2 * side;
+
+        Self {
+            front_line,
+            direction,
+            columns,
+            formation: Formation::Box,
+            slots: vec![None; columns * rows],
+        }
+    }
+
+    pub fn slot_position(&self, slot: usize) -> Vec2 {
+        let row = slot / self.columns;
+        let column = slot % self.columns;
+
+        Vec2::new(
+            self.front_line + self.direction * row as f32 * UNIT_SPACING,
+            column as f32 * UNIT_SPACING,
+        )
+    }
+
+    pub fn slot_of(&self, id: usize) -> Option<usize> {
+        self.slots.iter().position(|unit| *unit == Some(id))
+    }
+
+    pub fn nearest_free_slot(&self, position: Vec2) -> Option<usize> {
+        (0..self.slots.len())
+            .filter(|slot| self.slots[*slot].is_none())
+            .min_by(|a, b| {
+                let distance_a = (self.slot_position(*a) - position).magnitude2();
+                let distance_b = (self.slot_position(*b) - position).magnitude2();
+                distance_a.total_cmp(&distance_b)
+            })
+    }
+
+    /// Puts the unit into the slot, it leaves the slot it has been in before
+    pub fn place(&mut self, id: usize, slot: usize) -> Result<(), String> {
+        match self.slots.get(slot) {
+            None => {
+                return Err(format!(
+                    "slot {} does not exist, there are {} slots",
+                    slot,
+                    self.slots.len()
+                ));
+            }
+            Some(Some(other_id)) if *other_id != id => {
+                return Err(format!("slot {} is taken by unit {}", slot, other_id));
+            }
+            Some(_) => {}
+        }
+
+        if let Some(previous) = self.slot_of(id) {
+            self.slots[previous] = None;
+        }
+        self.slots[slot] = Some(id);
+
+        Ok(())
+    }
+
+    /// Puts the unit into the next free slot of the current formation
+    pub fn place_next(&mut self, id: usize) -> Result<usize, String> {
+        let slot = self
+            .slot_order(self.formation)
+            .into_iter()
+            .find(|slot| self.slots[*slot].is_none())
+            .ok_or_else(|| format!("all {} slots are taken", self.slots.len()))?;
+
+        self.place(id, slot)?;
+        Ok(slot)
+    }
+
+    /// Exchanges the slots of two units
+    pub fn swap(&mut self, id: usize, other_id: usize) -> Result<(), String> {
+        let slot = self
+            .slot_of(id)
+            .ok_or_else(|| format!("unit {} is not placed", id))?;
+        let other_slot = self
+            .slot_of(other_id)
+            .ok_or_else(|| format!("unit {} is not placed", other_id))?;
+
+        self.slots.swap(slot, other_slot);
+
+        Ok(())
+    }
+
+    /// Rearranges the placed units, the front units stay in front
+    pub fn set_formation(&mut self, formation: Formation) {
+        let units: Vec<usize> = self.slots.iter().flatten().copied().collect();
+
+        self.slots.fill(None);
+        for (id, slot) in units.into_iter().zip(self.slot_order(formation)) {
+            self.slots[slot] = Some(id);
+        }
+
+        self.formation = formation;
+    }
+
+    /// All placed units with their slots
+    pub fn units(&self) -> impl Iterator<Item = (usize, usize)> + '_ {
+        self.slots
+            .iter()
+            .enumerate()
+            .filter_map(|(slot, unit)| unit.map(|id| (id, slot)))
+    }
+
+    /// All slots, the ones filled first by the formation first
+    fn slot_order(&self, formation: Formation) -> Vec<usize> {
+        let mut slots: Vec<usize> = (0..self.slots.len()).collect();
+        slots.sort_by_key(|slot| {
+            let row = slot / self.columns;
+            let column = slot % self.columns;
+            // distance to the center in half columns
+            let offset = (2 * column).abs_diff(self.columns - 1);
+
+            let layer = match formation {
+                Formation::Line => 0,
+                Formation::Wedge => usize::from(offset > 2 * row + 1),
+                Formation::Box => row.max(offset),
+                // every other row and column, the two center columns are never both taken
+                Formation::Spread => usize::from(row % 2 == 1 || column % 2 == 1),
+            };
+            (layer, row, offset)
+        });
+
+        slots
+    }
+}
+
+#[test]
+fn test_formations_keep_the_units_in_distinct_slots() {
+    let mut grid = FormationGrid::new(Faction::Blue, 9);
+    for id in 0..9 {
+        grid.place_next(id).unwrap();
+    }
+
+    for formation in [
+        Formation::Line,
+        Formation::Wedge,
+        Formation::Box,
+        Formation::Spread,
+    ] {
+        grid.set_formation(formation);
+
+        let mut ids: Vec<usize> = grid.units().map(|(id, _)| id).collect();
+        ids.sort();
+        assert_eq!(ids, (0..9).collect::<Vec<_>>());
+    }
+
+    // no unit of the spread has a neighbour, not even diagonally
+    grid.set_formation(Formation::Spread);
+    let cells: Vec<(usize, usize)> = grid
+        .units()
+        .map(|(_, slot)| (slot / grid.columns, slot % grid.columns))
+        .collect();
+    for (i, (row, column)) in cells.iter().enumerate() {
+        for (other_row, other_column) in &cells[i + 1..] {
+            assert!(row.abs_diff(*other_row) > 1 || column.abs_diff(*other_column) > 1);
+        }
+    }
+
+    // the tip of the wedge are the two center slots of the front row
+    grid.set_formation(Formation::Wedge);
+    let front_units = grid
+        .units()
+        .filter(|(_, slot)| *slot < grid.columns)
+        .count();
+    assert_eq!(front_units, 2);
+
+    let slot_0 = grid.slot_of(0).unwrap();
+    let slot_1 = grid.slot_of(1).unwrap();
+    assert!(grid.place(0, slot_1).is_err());
+    grid.swap(0, 1).unwrap();
+    assert_eq!(grid.slot_of(0), Some(slot_1));
+    assert_eq!(grid.slot_of(1), Some(slot_0));
+}
@@ -1,44 +1,318 @@
 //! Definition of the game board
 
-use crate::ant_ai::WorldInterface;
+use cgmath::InnerSpace;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    ant_ai::{AntBodyInterface, WorldInterface},
+    ant_unit::AntUnit,
+    spatial_grid::SpatialGrid,
+};
 
 type Vec2 = cgmath::Vector2<f32>;
 
-#[derive(PartialEq, Clone, Copy)]
+#[derive(PartialEq, Clone, Copy, Debug, Serialize, Deserialize)]
 pub enum Faction {
     Red,
     Blue,
 }
 
-#[derive(Clone, Copy)]
+/// Rules an ant picks its next target by
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TargetStrategy {
+    Closest,
+    LowestHealth,
+    HighestThreat,
+    FurthestBack,    // the enemy furthest away in range, usually in the back of its army
+    CurrentAttacker, // the enemy which has hit the ant last, otherwise the closest
+}
+
+#[derive(Clone, Copy, Serialize, Deserialize)]
 pub struct Agent {
+    pub id: usize,
     pub faction: Faction,
     pub position: Vec2,
+    pub is_alive: bool,
+    pub health: f32,
+    pub max_health: f32,
+    pub threat: f32, // damage per tick while shooting
+    pub last_attacker_id: Option<usize>,
 }
 
+#[derive(Clone, Serialize, Deserialize)]
 pub struct GameBoard {
     pub agents: Vec<Agent>,
+
+    // Derived from the agents whenever they change
+    #[serde(skip)]
+    agent_indices: Vec<Option<usize>>, // index into the agents for every id
+    #[serde(skip)]
+    positions: Vec<Vec2>,
+    #[serde(skip)]
+    grid: SpatialGrid,
 }
 
 impl GameBoard {
     pub fn new() -> Self {
-        let agents: Vec<Agent> = vec![
-            Agent {
-                faction: Faction::Blue,
-                position: Vec2::new(0.0, 0.0),
-            },
-            Agent {
-                faction: Faction::Red,
-                position: Vec2::new(0.0, 10.0),
-            },
-        ];
+        let agents: Vec<Agent> = Vec::new();
+        let agent_indices = Vec::new();
+        let positions = Vec::new();
+        let grid = SpatialGrid::new();
+
+        Self {
+            agents,
+            agent_indices,
+            positions,
+            grid,
+        }
+    }
+
+    pub fn set_agents(&mut self, agents: Vec<Agent>) {
+        self.agents = agents;
+        self.rebuild_index();
+    }
+
+    fn rebuild_index(&mut self) {
+        self.agent_indices.clear();
+        self.positions.clear();
+
+        for (i, agent) in self.agents.iter().enumerate() {
+            if agent.id >= self.agent_indices.len() {
+                self.agent_indices.resize(agent.id + 1, None);
+            }
+            self.agent_indices[agent.id] = Some(i);
+            self.positions.push(agent.position);
+        }
+
+        self.grid.rebuild(&self.positions);
+    }
+
+    fn nearest_enemy(&self, position: Vec2, faction: Faction) -> Option<&Agent> {
+        let is_enemy = |index: usize| {
+            let agent = &self.agents[index];
+            agent.is_alive && agent.faction != faction
+        };
+
+        self.grid
+            .nearest(&self.positions, position, &is_enemy)
+            .map(|index| &self.agents[index])
+    }
+
+    /// Rebuilds the agents from the current state of the ants
+    pub fn update(&mut self, ants: &[AntUnit]) {
+        let agents = ants
+            .iter()
+            .map(|ant| Agent {
+                id: ant.id,
+                faction: ant.faction,
+                position: ant.controller.get_position(),
+                is_alive: ant.controller.is_alive(),
+                health: ant.controller.health(),
+                max_health: ant.controller.max_health(),
+                threat: ant.controller.threat(),
+                last_attacker_id: ant.last_attacker_id,
+            })
+            .collect();
 
-        Self { agents }
+        self.set_agents(agents);
     }
 }
 
 impl WorldInterface for GameBoard {
-    fn world_get_agents(&self) -> &[self::Agent] {
-        &self.agents
+    fn world_get_agent(&self, id: usize) -> Option<&self::Agent> {
+        let index = (*self.agent_indices.get(id)?)?;
+
+        Some(&self.agents[index])
+    }
+
+    fn world_get_nearest_enemy(&self, position: Vec2, faction: Faction) -> Option<&Agent> {
+        self.nearest_enemy(position, faction)
+    }
+
+    fn world_get_agents_in_radius(&self, position: Vec2, radius: f32, agents: &mut Vec<Agent>) {
+        let mut indices = Vec::new();
+        self.grid
+            .within_radius(&self.positions, position, radius, &mut indices);
+
+        agents.extend(indices.into_iter().map(|index| self.agents[index]));
     }
+
+    fn world_get_agents_in_cone(
+        &self,
+        position: Vec2,
+        direction: Vec2,
+        half_angle: cgmath::Rad<f32>,
+        radius: f32,
+        agents: &mut Vec<Agent>,
+    ) {
+        let mut indices = Vec::new();
+        self.grid
+            .within_radius(&self.positions, position, radius, &mut indices);
+
+        if direction.magnitude2() == 0.0 {
+            agents.extend(indices.into_iter().map(|index| self.agents[index]));
+            return;
+        }
+
+        let direction = direction.normalize();
+        let min_cos = half_angle.0.cos();
+        agents.extend(
+            indices
+                .into_iter()
+                .map(|index| self.agents[index])
+                .filter(|agent| {
+                    let offset = agent.position - position;
+                    let distance = offset.magnitude();
+
+                    // the agent at the tip of the cone is included
+                    distance == 0.0 || offset.dot(direction) >= min_cos * distance
+                }),
+        );
+    }
+
+    fn world_find_target(
+        &self,
+        seeker_id: usize,
+        strategy: TargetStrategy,
+        radius: f32,
+    ) -> Option<&Agent> {
+        let seeker = self.world_get_agent(seeker_id)?;
+
+        let is_enemy = |agent: &&Agent| agent.is_alive && agent.faction != seeker.faction;
+        let distance = |agent: &Agent| (agent.position - seeker.position).magnitude2();
+
+        // only the cells around the seeker are compared, in the order of the agents for the ties
+        let mut indices = Vec::new();
+        if matches!(
+            strategy,
+            TargetStrategy::LowestHealth
+                | TargetStrategy::HighestThreat
+                | TargetStrategy::FurthestBack
+        ) {
+            self.grid
+                .within_radius(&self.positions, seeker.position, radius, &mut indices);
+        }
+        let enemies = indices
+            .into_iter()
+            .map(|index| &self.agents[index])
+            .filter(is_enemy);
+
+        // ties are resolved by the distance, the first agent wins if it is a tie as well
+        let target = match strategy {
+            TargetStrategy::Closest => None,
+            TargetStrategy::LowestHealth => enemies.min_by(|a, b| {
+                a.health
+                    .total_cmp(&b.health)
+                    .then(distance(a).total_cmp(&distance(b)))
+            }),
+            TargetStrategy::HighestThreat => enemies.min_by(|a, b| {
+                b.threat
+                    .total_cmp(&a.threat)
+                    .then(distance(a).total_cmp(&distance(b)))
+            }),
+            TargetStrategy::FurthestBack => {
+                enemies.min_by(|a, b| distance(b).total_cmp(&distance(a)))
+            }
+            TargetStrategy::CurrentAttacker => seeker
+                .last_attacker_id
+                .and_then(|id| self.world_get_agent(id))
+                .filter(is_enemy),
+        };
+
+        target.or_else(|| self.nearest_enemy(seeker.position, seeker.faction))
+    }
+}
+
+#[test]
+fn test_target_strategies() {
+    let mut agents = Vec::new();
+    for (id, faction, x, health, threat) in [
+        (0, Faction::Blue, 0.0, 100.0, 1.0),
+        (1, Faction::Red, 10.0, 100.0, 1.0),
+        (2, Faction::Red, 20.0, 30.0, 1.0),
+        (3, Faction::Red, 30.0, 100.0, 5.0),
+        (4, Faction::Blue, 1.0, 100.0, 9.0),
+    ] {
+        agents.push(Agent {
+            id,
+            faction,
+            position: Vec2::new(x, 0.0),
+            is_alive: true,
+            health,
+            max_health: 100.0,
+            threat,
+            last_attacker_id: None,
+        });
+    }
+    // the weakest and most threatening enemy is out of the radius
+    agents.push(Agent {
+        id: 5,
+        faction: Faction::Red,
+        position: Vec2::new(200.0, 0.0),
+        is_alive: true,
+        health: 10.0,
+        max_health: 100.0,
+        threat: 20.0,
+        last_attacker_id: None,
+    });
+    agents[0].last_attacker_id = Some(3);
+
+    let mut game_board = GameBoard::new();
+    game_board.set_agents(agents);
+
+    let target = |strategy| {
+        game_board
+            .world_find_target(0, strategy, 50.0)
+            .map(|agent| agent.id)
+    };
+    assert_eq!(target(TargetStrategy::Closest), Some(1));
+    assert_eq!(target(TargetStrategy::LowestHealth), Some(2));
+    assert_eq!(target(TargetStrategy::HighestThreat), Some(3));
+    assert_eq!(target(TargetStrategy::FurthestBack), Some(3));
+    assert_eq!(target(TargetStrategy::CurrentAttacker), Some(3));
+
+    // without an enemy in the radius the closest one is taken
+    let target = game_board.world_find_target(0, TargetStrategy::LowestHealth, 5.0);
+    assert_eq!(target.map(|agent| agent.id), Some(1));
+}
+
+#[test]
+fn test_cone_query() {
+    let agents = [(0.0, 0.0), (5.0, 1.0), (5.0, 5.0), (-5.0, 0.0), (20.0, 0.0)]
+        .into_iter()
+        .enumerate()
+        .map(|(id, (x, y))| Agent {
+            id,
+            faction: Faction::Red,
+            position: Vec2::new(x, y),
+            is_alive: true,
+            health: 100.0,
+            max_health: 100.0,
+            threat: 1.0,
+            last_attacker_id: None,
+        })
+        .collect();
+
+    let mut game_board = GameBoard::new();
+    game_board.set_agents(agents);
+
+    let cone = |direction| {
+        let mut agents = Vec::new();
+        game_board.world_get_agents_in_cone(
+            Vec2::new(0.0, 0.0),
+            direction,
+            cgmath::Rad(0.5),
+            10.0,
+            &mut agents,
+        );
+        let mut ids: Vec<usize> = agents.iter().map(|agent| agent.id).collect();
+        ids.sort();
+        ids
+    };
+    assert_eq!(cone(Vec2::new(1.0, 0.0)), vec![0, 1]);
+    assert_eq!(cone(Vec2::new(-2.0, 0.0)), vec![0, 3]);
+
+    // without a direction the whole circle is taken
+    assert_eq!(cone(Vec2::new(0.0, 0.0)), vec![0, 1, 2, 3]);
 }
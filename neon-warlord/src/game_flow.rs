@@ -0,0 +1,332 @@
+//! Rounds of the auto battler, each round consists of a preparation, a battle and a resolution phase
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    battle_result::BattleResult,
+    formation::{Formation, FormationGrid},
+    game_board::Faction,
+    scenario::{Scenario, UnitSpawn},
+    unit_definition::UnitDefinition,
+    worker::TICKS_PER_SECOND,
+};
+
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
+pub enum GamePhase {
+    Preparation, // the player buys and places units
+    Battle,      // the ants are controlled by their ai
+    Resolution,  // the outcome of the battle is shown
+    GameOver,
+}
+
+/// Input of the player
+#[derive(Clone, Serialize, Deserialize)]
+pub enum GameCommand {
+    BuyUnit {
+        unit_type: String,
+    },
+    #[allow(dead_code)] // not yet bound to an input
+    PlaceUnit {
+        id: usize,
+        slot: usize,
+    },
+    #[allow(dead_code)] // not yet bound to an input
+    SwapUnits {
+        id: usize,
+        other_id: usize,
+    },
+    SetFormation(Formation),
+    StartBattle,
+}
+
+/// The state of the player, as shown by the ui
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+pub struct GameState {
+    pub phase: GamePhase,
+    pub round: u32,
+    pub gold: u32,
+    pub health: u32,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct RoundResult {
+    pub round: u32,
+    pub winner: Option<Faction>, // None if the battle is a draw or ran out of time
+    pub gold_earned: u32,
+    pub health_lost: u32,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct GameFlow {
+    state: GameState,
+
+    player_faction: Faction,
+    player_army: Vec<UnitSpawn>,
+    enemy_army: Vec<UnitSpawn>,
+    formation_grid: FormationGrid, // slots of the player army
+
+    max_nr_units: usize,
+
+    // GamePhase::Battle
+    battle_start_tick: u64,
+
+    // GamePhase::Resolution
+    resolution_start_tick: u64,
+}
+
+impl GameFlow {
+    const START_GOLD: u32 = 10;
+    const START_HEALTH: u32 = 20;
+    const ROUND_GOLD: u32 = 5;
+    const WIN_GOLD: u32 = 3;
+
+    const MAX_BATTLE_DURATION: u64 = 120 * TICKS_PER_SECOND;
+    const RESOLUTION_DURATION: u64 = 3 * TICKS_PER_SECOND;
+
+    pub fn new(scenario: &Scenario, player_faction: Faction, max_nr_units: usize) -> Self {
+        let state = GameState {
+            phase: GamePhase::Preparation,
+            round: 1,
+            gold: Self::START_GOLD,
+            health: Self::START_HEALTH,
+        };
+
+        let (player_army, enemy_army): (Vec<UnitSpawn>, Vec<UnitSpawn>) = scenario
+            .units
+            .iter()
+            .cloned()
+            .partition(|unit| unit.faction == player_faction);
+
+        // the units of the scenario keep their positions until they are moved
+        let mut formation_grid = FormationGrid::new(player_faction, max_nr_units);
+        for unit in &player_army {
+            if let Some(slot) = formation_grid.nearest_free_slot(unit.position) {
+                formation_grid
+                    .place(unit.id, slot)
+                    .expect("the slot is free");
+            }
+        }
+
+        Self {
+            state,
+            player_faction,
+            player_army,
+            enemy_army,
+            formation_grid,
+            max_nr_units,
+            battle_start_tick: 0,
+            resolution_start_tick: 0,
+        }
+    }
+
+    pub fn state(&self) -> GameState {
+        self.state
+    }
+
+    pub fn phase(&self) -> GamePhase {
+        self.state.phase
+    }
+
+    pub fn max_nr_units(&self) -> usize {
+        self.max_nr_units
+    }
+
+    /// All units taking part in the next battle
+    pub fn units(&self) -> impl Iterator<Item = &UnitSpawn> {
+        self.player_army.iter().chain(&self.enemy_army)
+    }
+
+    /// Adds a unit to the next free slot of the army of the player and returns its id
+    pub fn buy_unit(&mut self, unit: &UnitDefinition) -> Result<usize, String> {
+        self.expect_phase(GamePhase::Preparation)?;
+
+        if self.state.gold < unit.cost {
+            return Err(format!(
+                "not enough gold, {} costs {} but only {} is left",
+                unit.name, unit.cost, self.state.gold
+            ));
+        }
+
+        let id = (0..self.max_nr_units)
+            .find(|id| self.units().all(|unit| unit.id != *id))
+            .ok_or_else(|| format!("the army is full, at most {} units", self.max_nr_units))?;
+        let slot = self.formation_grid.place_next(id)?;
+
+        self.state.gold -= unit.cost;
+        self.player_army.push(UnitSpawn {
+            id,
+            unit_type: unit.name.clone(),
+            faction: self.player_faction,
+            position: self.formation_grid.slot_position(slot),
+        });
+
+        Ok(id)
+    }
+
+    /// Moves a unit of the player to the slot it starts the next battle at
+    pub fn place_unit(&mut self, id: usize, slot: usize) -> Result<(), String> {
+        self.expect_phase(GamePhase::Preparation)?;
+        self.expect_player_unit(id)?;
+
+        self.formation_grid.place(id, slot)?;
+        self.update_positions_of(&[id]);
+
+        Ok(())
+    }
+
+    /// Exchanges the slots of two units of the player
+    pub fn swap_units(&mut self, id: usize, other_id: usize) -> Result<(), String> {
+        self.expect_phase(GamePhase::Preparation)?;
+        self.expect_player_unit(id)?;
+        self.expect_player_unit(other_id)?;
+
+        self.formation_grid.swap(id, other_id)?;
+        self.update_positions_of(&[id, other_id]);
+
+        Ok(())
+    }
+
+    /// Rearranges the whole army of the player
+    pub fn set_formation(&mut self, formation: Formation) -> Result<(), String> {
+        self.expect_phase(GamePhase::Preparation)?;
+
+        self.formation_grid.set_formation(formation);
+        let ids: Vec<usize> = self.formation_grid.units().map(|(id, _)| id).collect();
+        self.update_positions_of(&ids);
+
+        Ok(())
+    }
+
+    pub fn start_battle(&mut self, tick: u64) -> Result<(), String> {
+        self.expect_phase(GamePhase::Preparation)?;
+
+        self.battle_start_tick = tick;
+        self.state.phase = GamePhase::Battle;
+
+        Ok(())
+    }
+
+    pub fn battle_duration(&self, tick: u64) -> u64 {
+        tick - self.battle_start_tick
+    }
+
+    pub fn is_battle_timed_out(&self, tick: u64) -> bool {
+        self.battle_duration(tick) >= Self::MAX_BATTLE_DURATION
+    }
+
+    /// Awards gold and applies the health loss, the player loses one health per surviving enemy
+    pub fn finish_battle(&mut self, result: &BattleResult, tick: u64) -> RoundResult {
+        let winner = if result.is_finished {
+            result.winner
+        } else {
+            None
+        };
+
+        let mut gold_earned = Self::ROUND_GOLD;
+        let mut health_lost = 0;
+        if winner == Some(self.player_faction) {
+            gold_earned += Self::WIN_GOLD;
+        } else if winner.is_some() {
+            health_lost = result.survivors().count() as u32;
+        }
+
+        self.state.gold += gold_earned;
+        self.state.health = self.state.health.saturating_sub(health_lost);
+
+        self.resolution_start_tick = tick;
+        self.state.phase = GamePhase::Resolution;
+
+        RoundResult {
+            round: self.state.round,
+            winner,
+            gold_earned,
+            health_lost,
+        }
+    }
+
+    pub fn is_resolution_done(&self, tick: u64) -> bool {
+        tick - self.resolution_start_tick >= Self::RESOLUTION_DURATION
+    }
+
+    /// Advances the round counter, the game is over once the player has no health left
+    pub fn start_next_round(&mut self) {
+        if self.state.health == 0 {
+            self.state.phase = GamePhase::GameOver;
+            return;
+        }
+
+        self.state.round += 1;
+        self.state.phase = GamePhase::Preparation;
+    }
+
+    /// The units start the next battle at their slots
+    fn update_positions_of(&mut self, ids: &[usize]) {
+        for unit in &mut self.player_army {
+            if ids.contains(&unit.id)
+                && let Some(slot) = self.formation_grid.slot_of(unit.id)
+            {
+                unit.position = self.formation_grid.slot_position(slot);
+            }
+        }
+    }
+
+    fn expect_player_unit(&self, id: usize) -> Result<(), String> {
+        if self.player_army.iter().any(|unit| unit.id == id) {
+            Ok(())
+        } else {
+            Err(format!("unit {} does not belong to the player", id))
+        }
+    }
+
+    fn expect_phase(&self, phase: GamePhase) -> Result<(), String> {
+        if self.state.phase == phase {
+            Ok(())
+        } else {
+            Err(format!(
+                "only possible during {:?}, the game is in {:?}",
+                phase, self.state.phase
+            ))
+        }
+    }
+}
+
+#[test]
+fn test_round_awards_gold_and_costs_health() {
+    use crate::battle_result::UnitResult;
+
+    let unit_definitions = crate::unit_definition::UnitDefinitions::load_default().unwrap();
+    let ant = unit_definitions.get("ant").unwrap();
+
+    let scenario = Scenario { units: Vec::new() };
+    let mut game_flow = GameFlow::new(&scenario, Faction::Blue, 4);
+
+    let id = game_flow.buy_unit(ant).unwrap();
+    assert_eq!(game_flow.state().gold, GameFlow::START_GOLD - ant.cost);
+
+    game_flow.start_battle(0).unwrap();
+    assert!(game_flow.buy_unit(ant).is_err());
+
+    let survivor = UnitResult {
+        id: id + 1,
+        unit_type: "ant".to_string(),
+        faction: Faction::Red,
+        is_alive: true,
+        health: 100.0,
+        damage_dealt: 0.0,
+        damage_received: 0.0,
+    };
+    let result = BattleResult {
+        is_finished: true,
+        winner: Some(Faction::Red),
+        ticks: 100,
+        units: vec![survivor],
+    };
+
+    let round_result = game_flow.finish_battle(&result, 100);
+    assert_eq!(round_result.health_lost, 1);
+    assert_eq!(game_flow.state().health, GameFlow::START_HEALTH - 1);
+
+    game_flow.start_next_round();
+    assert_eq!(game_flow.phase(), GamePhase::Preparation);
+    assert_eq!(game_flow.state().round, 2);
+}
@@ -0,0 +1,122 @@
+//! Runs a battle on the worker without a window and without the renderer
+
+use std::sync::mpsc;
+
+use crate::{
+    battle_event::BattleLog,
+    battle_result::BattleResult,
+    scenario::Scenario,
+    unit_definition::UnitDefinitions,
+    worker::{Worker, WorkerMessage},
+};
+
+/// Free unit ids for the units summoned or spawned by the waves during the battle
+const MAX_NR_SPAWNED_UNITS: usize = 64;
+
+pub struct HeadlessBattle {
+    worker: Worker,
+    channel_1_rx: mpsc::Receiver<WorkerMessage>,
+
+    tick: u64,
+    battle_log: BattleLog,
+}
+
+impl HeadlessBattle {
+    pub fn new(scenario: Scenario, unit_definitions: UnitDefinitions, max_nr_orbs: usize) -> Self {
+        // nobody talks to the worker, only its battle events are kept
+        let (_channel_0_tx, channel_0_rx) = mpsc::channel();
+        let (channel_1_tx, channel_1_rx) = mpsc::channel();
+
+        let max_nr_ants = scenario.units.len() + MAX_NR_SPAWNED_UNITS;
+        let mut worker = Worker::new(
+            channel_0_rx,
+            channel_1_tx,
+            scenario,
+            unit_definitions,
+            max_nr_ants,
+            max_nr_orbs,
+        );
+        let tick = 0;
+        let battle_log = BattleLog::new();
+
+        // skip the preparation phase, the armies are already placed
+        worker
+            .start_battle(tick)
+            .expect("a new game starts in the preparation phase");
+
+        Self {
+            worker,
+            channel_1_rx,
+            tick,
+            battle_log,
+        }
+    }
+
+    /// Steps the worker as fast as possible until the battle is finished or max_ticks is reached
+    pub fn run(&mut self, max_ticks: u64) -> BattleResult {
+        while self.tick < max_ticks {
+            // the worker ends the round on the update after the battle is finished
+            let is_finished = self.worker.is_battle_finished();
+
+            self.worker.update(self.tick);
+            self.tick += 1;
+
+            for message in self.channel_1_rx.try_iter() {
+                if let WorkerMessage::BattleEvents(events) = message {
+                    self.battle_log.extend(&events);
+                }
+            }
+
+            if is_finished {
+                break;
+            }
+        }
+
+        self.worker.get_battle_result(self.tick)
+    }
+
+    pub fn battle_log(&self) -> &BattleLog {
+        &self.battle_log
+    }
+}
+
+#[test]
+fn test_battle_is_deterministic() {
+    let ant_generator = crate::ant_generator::AntGenerator::new(16);
+    let scenario = Scenario::from_ant_generator(&ant_generator);
+
+    let unit_definitions = UnitDefinitions::load_default().unwrap();
+
+    let result_0 = HeadlessBattle::new(scenario.clone(), unit_definitions.clone(), 16).run(20_000);
+    let result_1 = HeadlessBattle::new(scenario, unit_definitions, 16).run(20_000);
+
+    assert_eq!(result_0.ticks, result_1.ticks);
+    assert!(result_0.winner == result_1.winner);
+    for (unit_0, unit_1) in result_0.units.iter().zip(&result_1.units) {
+        assert_eq!(unit_0.health, unit_1.health);
+        assert_eq!(unit_0.damage_dealt, unit_1.damage_dealt);
+    }
+}
+
+#[test]
+fn test_brood_ant_summons_in_a_headless_battle() {
+    use crate::{
+        game_board::Faction,
+        scenario::{Army, Scenario},
+    };
+
+    let armies = [
+        Army::parse(Faction::Blue, "brood_ant:2").unwrap(),
+        Army::parse(Faction::Red, "heavy_ant:4").unwrap(),
+    ];
+    let scenario = Scenario::from_armies(&armies, 0);
+    let nr_units = scenario.units.len();
+    let unit_definitions = UnitDefinitions::load_default().unwrap();
+
+    let mut battle = HeadlessBattle::new(scenario, unit_definitions, 16);
+    let result = battle.run(20_000);
+
+    let json_lines = battle.battle_log().to_json_lines();
+    assert!(json_lines.contains("\"ability\":\"summon\""));
+    assert!(result.units.len() > nr_units);
+}
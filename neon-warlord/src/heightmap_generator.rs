@@ -3,8 +3,21 @@
 use forward_renderer::{HeightMap, TerrainTextureDetails};
 use noise::NoiseFn;
 
+type Vec2 = cgmath::Vector2<f32>;
+type Vec3 = cgmath::Vector3<f32>;
+
+/// Seed of the terrain of a new game
+const TERRAIN_SEED: u32 = 1;
+
+/// Tiles along a side of a heightmap chunk, the points of the finest chunks are one unit apart
+pub const NR_TILES: usize = 32;
+
+/// Chunks starting below this y are flat
+const FLAT_CHUNK_Y: isize = -10;
+
 #[allow(unused)]
 pub struct HeightMapGenerator {
+    seed: u32,
     perlin: noise::Perlin,
 }
 
@@ -17,9 +30,17 @@ impl Default for HeightMapGenerator {
 #[allow(unused)]
 impl HeightMapGenerator {
     pub fn new() -> Self {
-        let perlin: noise::Perlin = noise::Perlin::new(1);
+        Self::with_seed(TERRAIN_SEED)
+    }
 
-        Self { perlin }
+    pub fn with_seed(seed: u32) -> Self {
+        let perlin: noise::Perlin = noise::Perlin::new(seed);
+
+        Self { seed, perlin }
+    }
+
+    pub fn seed(&self) -> u32 {
+        self.seed
     }
 
     pub fn generate(&self, details: &TerrainTextureDetails) -> HeightMap {
@@ -31,50 +52,22 @@ impl HeightMapGenerator {
 
         let size = size_x * size_y;
 
+        if p_y < FLAT_CHUNK_Y {
+            return HeightMap {
+                heights: vec![0.0; size],
+                details: details.clone(),
+            };
+        }
+
         let mut heights = Vec::with_capacity(size);
         for y in 0..size_y {
             for x in 0..size_x {
-                let mut height = (self.perlin.get([
-                    (p_x + x as isize * distance as isize) as f64 / 128.0,
-                    (p_y + y as isize * distance as isize) as f64 / 128.0,
-                ]) * 20.0)
-                    .max(0.0);
-
-                height += (self.perlin.get([
-                    (p_x + x as isize * distance as isize) as f64 / 64.0,
-                    (p_y + y as isize * distance as isize) as f64 / 64.0,
-                ]) * 20.0)
-                    .max(0.0);
-
-                height += (self.perlin.get([
-                    (p_x + x as isize * distance as isize) as f64 / 32.0,
-                    (p_y + y as isize * distance as isize) as f64 / 32.0,
-                ]) * 20.0)
-                    .max(0.0);
-
-                height += (self.perlin.get([
-                    (p_x + x as isize * distance as isize) as f64 / 16.0,
-                    (p_y + y as isize * distance as isize) as f64 / 16.0,
-                ]) * 8.0)
-                    .max(0.0);
-
-                height += (self.perlin.get([
-                    (p_x + x as isize * distance as isize) as f64 / 8.0,
-                    (p_y + y as isize * distance as isize) as f64 / 8.0,
-                ]) * 2.0)
-                    .max(0.0);
-
-                // create canyon
-                let a = Self::depth_to_distance(7, 8);
-                height *= Self::canyon(
-                    (p_y + y as isize * distance as isize - a as isize / 2) as f32 / 30.0,
-                ) as f64;
-
-                if p_y < -10 {
-                    height = 0.0;
-                }
-
-                heights.push(height as f32);
+                let height = self.sample(
+                    p_x + x as isize * distance as isize,
+                    p_y + y as isize * distance as isize,
+                );
+
+                heights.push(height);
                 // heights.push(0.0);
             }
         }
@@ -85,6 +78,74 @@ impl HeightMapGenerator {
         }
     }
 
+    /// Height of the terrain, interpolated between the points of the finest heightmap
+    pub fn height(&self, position: Vec2) -> f32 {
+        let (h, fx, fy) = self.cell(position);
+
+        let h0 = h[0] + (h[1] - h[0]) * fx;
+        let h1 = h[2] + (h[3] - h[2]) * fx;
+        h0 + (h1 - h0) * fy
+    }
+
+    /// Rise of the terrain per unit along x and y
+    pub fn gradient(&self, position: Vec2) -> Vec2 {
+        let (h, fx, fy) = self.cell(position);
+
+        Vec2::new(
+            (h[1] - h[0]) * (1.0 - fy) + (h[3] - h[2]) * fy,
+            (h[2] - h[0]) * (1.0 - fx) + (h[3] - h[1]) * fx,
+        )
+    }
+
+    /// Position on the terrain surface
+    pub fn ground_position(&self, position: Vec2) -> Vec3 {
+        position.extend(self.height(position))
+    }
+
+    /// Heights of the corners of the cell around the position and the offset within the cell
+    fn cell(&self, position: Vec2) -> ([f32; 4], f32, f32) {
+        let x = position.x.floor();
+        let y = position.y.floor();
+        let fx = position.x - x;
+        let fy = position.y - y;
+        let (x, y) = (x as isize, y as isize);
+
+        // flat where the finest chunk around the position is flat, the chunks start one point
+        // before their first tile
+        let chunk_y = y.div_euclid(NR_TILES as isize) * NR_TILES as isize - 1;
+        if chunk_y < FLAT_CHUNK_Y {
+            return ([0.0; 4], fx, fy);
+        }
+
+        let heights = [
+            self.sample(x, y),
+            self.sample(x + 1, y),
+            self.sample(x, y + 1),
+            self.sample(x + 1, y + 1),
+        ];
+
+        (heights, fx, fy)
+    }
+
+    /// Height of a single point of the heightmap
+    fn sample(&self, x: isize, y: isize) -> f32 {
+        let octave = |scale: f64, amplitude: f64| {
+            (self.perlin.get([x as f64 / scale, y as f64 / scale]) * amplitude).max(0.0)
+        };
+
+        let mut height = octave(128.0, 20.0)
+            + octave(64.0, 20.0)
+            + octave(32.0, 20.0)
+            + octave(16.0, 8.0)
+            + octave(8.0, 2.0);
+
+        // create canyon
+        let a = Self::depth_to_distance(7, 8);
+        height *= Self::canyon((y - a as isize / 2) as f32 / 30.0) as f64;
+
+        height as f32
+    }
+
     fn canyon(x: f32) -> f32 {
         1.0 - 1.0 / (1.0 + x * x * x * x * x * x)
     }
@@ -94,3 +155,41 @@ impl HeightMapGenerator {
         2usize.pow(exponent as u32)
     }
 }
+
+#[test]
+fn test_height_matches_the_finest_chunks() {
+    let generator = HeightMapGenerator::new();
+
+    // a finest chunk starting one point before its square at (0, square_y)
+    let chunk = |square_y: isize| {
+        let details = TerrainTextureDetails {
+            pos_0: cgmath::Vector2::new(-1, square_y - 1),
+            pos_1: cgmath::Vector2::new(0, square_y),
+            point_distance: 1,
+            size_0: NR_TILES + 3,
+            size_1: NR_TILES + 1,
+            nr_tiles: NR_TILES,
+            depth: 0,
+            node_index: 0,
+        };
+        generator.generate(&details).heights
+    };
+
+    // the chunk below the x axis starts before y = -10 and is flat, also where it reaches above it
+    assert!(
+        chunk(-(NR_TILES as isize))
+            .iter()
+            .all(|height| *height == 0.0)
+    );
+    assert_eq!(generator.height(Vec2::new(20.0, -5.0)), 0.0);
+
+    let size_0 = NR_TILES + 3;
+    let heights = chunk(0);
+    assert!(heights.iter().any(|height| *height > 0.0));
+    for y in 1..size_0 - 1 {
+        for x in 1..size_0 - 1 {
+            let position = Vec2::new(x as f32 - 1.0, y as f32 - 1.0);
+            assert_eq!(generator.height(position), heights[y * size_0 + x]);
+        }
+    }
+}
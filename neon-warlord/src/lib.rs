@@ -1,28 +1,44 @@
 //! Creates the Neon-Warlord application
 
+mod ability;
 mod ant_ai;
 mod ant_controller;
 mod ant_generator;
 mod ant_state;
 mod ant_storage;
+mod ant_unit;
+mod balance;
+mod battle_event;
+mod battle_result;
 mod camera_controller;
+mod combat;
 mod debug_overlay;
+mod formation;
 mod game_board;
+mod game_flow;
+mod headless;
 mod heightmap_generator;
+mod navigation;
 mod orb_controller;
 mod orb_storage;
 mod procedural_tree;
+mod replay;
+mod resources;
+mod save_game;
+mod scenario;
 mod settings;
 mod simple_physics_simulation;
+mod spatial_grid;
+mod status_effect;
 mod sun_storage;
+mod unit_definition;
 mod verlet_physics;
+mod wave;
 mod worker;
 mod worker_instance;
 
 use forward_renderer::{
-    AnimatedObjectStorage, ForwardRenderer, PerformanceMonitor, TerrainStorage,
-    glow_storage::GlowStorage, particle_storage::ParticleStorage,
-    plasma_orb_storage::PlasmaOrbStorage,
+    ForwardRenderer, PerformanceMonitor, TerrainStorage, animation_shader::AnimationShaderDraw,
 };
 use instant::Instant;
 #[cfg(target_arch = "wasm32")]
@@ -41,16 +57,35 @@ use winit::event::{ElementState, WindowEvent};
 
 use crate::{
     ant_controller::AntPosition, ant_generator::AntGenerator, ant_storage::AntStorage,
-    camera_controller::CameraController, debug_overlay::DebugOverlay,
-    simple_physics_simulation::SimplePhysicsSimulation, sun_storage::SunStorage,
-    worker::MainMessage, worker_instance::WorkerInstance,
+    balance::BalanceSettings, battle_event::BattleEventKind, battle_event::BattleLog,
+    camera_controller::CameraController, debug_overlay::DebugOverlay, formation::Formation,
+    game_board::Faction, game_flow::GameCommand, game_flow::GamePhase, headless::HeadlessBattle,
+    orb_storage::OrbStorage, replay::Replay, save_game::SaveGame, scenario::Army,
+    scenario::Scenario, simple_physics_simulation::SimplePhysicsSimulation,
+    sun_storage::SunStorage, unit_definition::UnitDefinitions, worker::MainMessage,
+    worker_instance::WorkerInstance,
 };
 
 const WATCH_POINTS_SIZE: usize = 10;
-const DEBUG_OVERLAY_SIZE: usize = 10;
+const DEBUG_OVERLAY_SIZE: usize = 12;
+
+/// Written with F5 and read with F9
+const SAVE_GAME_PATH: &str = "neon-warlord.save.json";
+
+/// Written with F6 and played back with F10
+const REPLAY_PATH: &str = "neon-warlord.replay.json";
+
+/// Written with F7, one battle event per line
+const BATTLE_LOG_PATH: &str = "neon-warlord.battle.jsonl";
+
+/// Battles of the balance runs are cut off after ten minutes
+const BALANCE_MAX_TICKS: u64 = 10 * 60 * worker::TICKS_PER_SECOND;
 
 struct ObjectSettings {
+    pub nr_start_ants: usize,
     pub max_nr_ants: usize,
+    pub max_nr_orbs: usize,
+    pub enemy_waves: bool, // the red ants of the generator are replaced by the waves of the worker
 }
 
 struct CameraSettings {
@@ -60,7 +95,7 @@ struct CameraSettings {
 }
 
 struct NeonWarlord {
-    _settings: settings::Settings,
+    settings: settings::Settings,
 
     // Render engine
     size: winit::dpi::PhysicalSize<u32>,
@@ -88,6 +123,14 @@ struct NeonWarlord {
     force: String,
     id: String,
 
+    // Game flow
+    game_state: String,
+    wave_progress: String,
+
+    // Battle events
+    battle_log: BattleLog,
+    kill_feed: String,
+
     // Terrain
     terrain: TerrainStorage,
 
@@ -98,20 +141,15 @@ struct NeonWarlord {
     // Sun
     sun: SunStorage,
 
-    // Particles
-    particles: ParticleStorage,
-    plasma_orbs: PlasmaOrbStorage,
-    glows: GlowStorage,
-
     // Orbs
-    // orbs: OrbStorage,
+    orbs: OrbStorage,
 
     // Simple physics simulation
     simple_physics_simulation: SimplePhysicsSimulation,
 
     // Worker
     worker: WorkerInstance,
-    ant_positions: [AntPosition; 1],
+    ant_positions: Vec<AntPosition>,
 }
 
 impl NeonWarlord {
@@ -168,25 +206,32 @@ impl NeonWarlord {
         let _mouse_pos_y = 0;
         let _mouse_pos_x = 0;
 
+        // unit definitions
+        let unit_definitions = match UnitDefinitions::load_default() {
+            Ok(unit_definitions) => unit_definitions,
+            Err(err) => panic!("{}", err),
+        };
+
         // create ant
-        // let glb_bin = include_bytes!("../res/wiggle_tower2.glb");
-        let glb_bin = include_bytes!("../res/ant_0_10.glb");
-        let animated_object_storage_ant = AnimatedObjectStorage::create_from_glb(
+        let mut ants = match AntStorage::new(
             renderer_interface,
             &renderer.animation_bind_group_layout,
-            glb_bin,
-            settings.get_object_settings().max_nr_ants,
-        );
-
-        let mut ants = AntStorage::new(
-            // point_light_storage_ant,
-            animated_object_storage_ant,
+            &unit_definitions,
             settings.get_object_settings().max_nr_ants,
-        );
-
-        let ant_generator = AntGenerator::new(settings.get_object_settings().max_nr_ants);
+        ) {
+            Ok(ants) => ants,
+            Err(err) => panic!("{}", err),
+        };
+
+        let ant_generator = AntGenerator::new(settings.get_object_settings().nr_start_ants);
+        let mut scenario = Scenario::from_ant_generator(&ant_generator);
+        if settings.get_object_settings().enemy_waves {
+            scenario.units.retain(|unit| unit.faction == Faction::Blue);
+        }
         for elem in &ant_generator.ants {
-            ants.set_ant(elem);
+            if scenario.units.iter().any(|unit| unit.id == elem.id) {
+                ants.set_ant(elem);
+            }
         }
 
         // terrain
@@ -200,21 +245,31 @@ impl NeonWarlord {
         // sun
         let sun = SunStorage::new(renderer_interface);
 
-        // Particles
-        let particles = ParticleStorage::new(renderer_interface, 1);
-        let plasma_orbs = PlasmaOrbStorage::new(renderer_interface, 1);
-        let glows = GlowStorage::new(renderer_interface, 1);
+        // Orbs
+        let orbs = OrbStorage::new(
+            renderer_interface,
+            settings.get_object_settings().max_nr_orbs,
+        );
 
         // Simple physics simulation
         let simple_physics_simulation = SimplePhysicsSimulation::new(renderer_interface);
 
         // Worker
-        let worker = WorkerInstance::new();
+        let worker = WorkerInstance::new(
+            scenario,
+            unit_definitions,
+            settings.get_object_settings().max_nr_ants,
+            settings.get_object_settings().max_nr_orbs,
+        );
 
-        let ant_positions = [AntPosition::new(); 1];
+        // the positions are set when the worker spawns the ants
+        let ant_positions = vec![
+            AntPosition::new(cgmath::Vector3::new(0.0, 0.0, 0.0));
+            settings.get_object_settings().max_nr_ants
+        ];
 
         Self {
-            _settings: settings,
+            settings,
             size,
             scale_factor,
             renderer,
@@ -236,16 +291,41 @@ impl NeonWarlord {
             location: String::new(),
             force: String::new(),
             id: String::new(),
+            game_state: String::new(),
+            wave_progress: String::new(),
+            battle_log: BattleLog::new(),
+            kill_feed: String::new(),
             sun,
-            particles,
-            plasma_orbs,
-            glows,
+            orbs,
             worker,
             ups: 0,
             ant_positions,
             simple_physics_simulation,
         }
     }
+
+    /// Replaces the worker by one playing back the replay
+    fn play_replay(&mut self, replay: Replay) {
+        let object_settings = self.settings.get_object_settings();
+        if replay.max_nr_ants > object_settings.max_nr_ants
+            || replay.max_nr_orbs != object_settings.max_nr_orbs
+        {
+            log::warn!(
+                "replay: recorded with {} ants and {} orbs, supported are {} ants and {} orbs",
+                replay.max_nr_ants,
+                replay.max_nr_orbs,
+                object_settings.max_nr_ants,
+                object_settings.max_nr_orbs
+            );
+            return;
+        }
+
+        // the ants of the replay are spawned with the first snapshot
+        self.worker = WorkerInstance::from_replay(replay);
+        self.ants.despawn_all();
+
+        log::info!("playing back {}", REPLAY_PATH);
+    }
 }
 
 #[allow(unused)]
@@ -374,16 +454,104 @@ impl DefaultApplicationInterfaceRuntime for NeonWarlord {
                         );
                     }
                     // ##########################################################
+                    worker::WorkerMessage::GameState(game_state) => {
+                        // the army is spawned again with the next snapshot
+                        if game_state.phase == GamePhase::Preparation {
+                            self.ants.despawn_all();
+                        }
+                        self.game_state = format!(
+                            "round {} {:?} gold {} health {}",
+                            game_state.round, game_state.phase, game_state.gold, game_state.health
+                        );
+                    }
+                    // ##########################################################
+                    worker::WorkerMessage::RoundFinished(round_result) => {
+                        let winner = match round_result.winner {
+                            Some(faction) => format!("{:?} wins", faction),
+                            None => "draw".to_string(),
+                        };
+                        log::info!(
+                            "round {} finished: {}, +{} gold, -{} health",
+                            round_result.round,
+                            winner,
+                            round_result.gold_earned,
+                            round_result.health_lost
+                        );
+                    }
+                    // ##########################################################
+                    worker::WorkerMessage::CommandRejected(err) => {
+                        log::warn!("{}", err);
+                    }
+                    // ##########################################################
+                    worker::WorkerMessage::GameSaved(save_game) => {
+                        match save_game.write_to_file(SAVE_GAME_PATH) {
+                            Ok(()) => log::info!("game saved to {}", SAVE_GAME_PATH),
+                            Err(err) => log::warn!("{}", err),
+                        }
+                    }
+                    // ##########################################################
+                    worker::WorkerMessage::GameLoaded(game_state) => {
+                        // the loaded ants are spawned with the next snapshot
+                        self.ants.despawn_all();
+                        self.game_state = format!(
+                            "round {} {:?} gold {} health {}",
+                            game_state.round, game_state.phase, game_state.gold, game_state.health
+                        );
+                        log::info!("game loaded from {}", SAVE_GAME_PATH);
+                    }
+                    // ##########################################################
+                    worker::WorkerMessage::LoadFailed(err) => {
+                        log::warn!("{}", err);
+                    }
+                    // ##########################################################
+                    worker::WorkerMessage::ReplaySaved(replay) => {
+                        match replay.write_to_file(REPLAY_PATH) {
+                            Ok(()) => log::info!("replay saved to {}", REPLAY_PATH),
+                            Err(err) => log::warn!("{}", err),
+                        }
+                    }
+                    // ##########################################################
+                    worker::WorkerMessage::BattleEvents(events) => {
+                        for event in &events {
+                            if let BattleEventKind::Death { unit_id, killer_id } = event.kind {
+                                self.kill_feed =
+                                    format!("ant {} killed ant {}", killer_id, unit_id);
+                            }
+                        }
+                        self.battle_log.extend(&events);
+                    }
+                    // ##########################################################
+                    worker::WorkerMessage::ReplayFinished => {
+                        log::info!("replay finished, the game continues with live input");
+                    }
+                    // ##########################################################
+                    worker::WorkerMessage::WaveProgress(progress) => {
+                        self.wave_progress = format!(
+                            "wave {} spawned {}/{} alive {}",
+                            progress.wave,
+                            progress.nr_spawned,
+                            progress.nr_units,
+                            progress.nr_alive
+                        );
+                    }
+                    // ##########################################################
                     worker::WorkerMessage::Snapshot(snapshot) => {
                         // transmitting the whole state ensures that a complete tick of the physics thread has been completed
+                        let snapshot_time_stamp = snapshot.time_stamp;
                         for elem in snapshot.ant_actions {
                             let index = elem.index;
                             match elem.action {
+                                // ##########################################################
+                                ant_controller::AntAction::Spawn(snapshot) => {
+                                    self.ant_positions[index] = AntPosition::new(snapshot.pos);
+                                    self.ant_positions[index].look_at = snapshot.look_at;
+                                    self.ants.spawn(index, snapshot.unit_type);
+                                }
                                 // ##########################################################
                                 ant_controller::AntAction::UpdatePosition(snapshot) => {
                                     self.ant_positions[index]
                                         .pos
-                                        .add(snapshot.pos, snapshot.time_stamp);
+                                        .add(snapshot.pos, snapshot_time_stamp);
                                     self.ant_positions[index].look_at = snapshot.look_at;
                                     self.ant_positions[index].is_final = false
                                 }
@@ -391,24 +559,49 @@ impl DefaultApplicationInterfaceRuntime for NeonWarlord {
                                 ant_controller::AntAction::FinalPosition(snapshot) => {
                                     self.ant_positions[index]
                                         .pos
-                                        .add(snapshot.pos, snapshot.time_stamp);
+                                        .add(snapshot.pos, snapshot_time_stamp);
                                     self.ant_positions[index].look_at = snapshot.look_at;
                                     self.ant_positions[index].is_final = true
                                 }
                                 // ##########################################################
                                 ant_controller::AntAction::SetAnimation(ant_animation) => {
-                                    let animation_index = match ant_animation {
-                                        ant_controller::AntAnimation::Idle => 0,
-                                        ant_controller::AntAnimation::Walk => 1,
-                                        ant_controller::AntAnimation::_ChargeShot => 0,
-                                    };
-
-                                    self.ants.set_animation(index, animation_index);
+                                    self.ants.set_animation(index, ant_animation);
                                 }
                                 // ##########################################################
                                 ant_controller::AntAction::SetAnimationSpeed(speed) => {
                                     self.ants.set_animation_speed(index, speed);
                                 }
+                                // ##########################################################
+                                ant_controller::AntAction::SetHealth(health) => {
+                                    self.ants.set_health(index, health);
+                                }
+                                // ##########################################################
+                                ant_controller::AntAction::SetStatusEffects(status_effects) => {
+                                    self.ants.set_status_effects(index, status_effects);
+                                }
+                                // ##########################################################
+                                ant_controller::AntAction::Die => {
+                                    self.ant_positions[index].is_final = true;
+                                    self.ants.set_dead(index);
+                                }
+                            }
+                        }
+
+                        for elem in snapshot.orb_actions {
+                            let index = elem.index;
+                            match elem.action {
+                                // ##########################################################
+                                orb_controller::OrbAction::SetPosition(pos) => {
+                                    self.orbs.set_position(index, pos);
+                                }
+                                // ##########################################################
+                                orb_controller::OrbAction::SetScale(scale) => {
+                                    self.orbs.set_size(index, scale);
+                                }
+                                // ##########################################################
+                                orb_controller::OrbAction::EnableCharge(enable) => {
+                                    self.orbs.enable_charge(index, enable);
+                                }
                             }
                         }
                     }
@@ -439,9 +632,7 @@ impl DefaultApplicationInterfaceRuntime for NeonWarlord {
         watch_index += 1;
         self.watch_fps.start(watch_index, "Update Particles");
         {
-            // self.particles.update(renderer_interface, dt);
-            // self.plasma_orbs.update(renderer_interface, dt);
-            // self.glows.update(renderer_interface, dt);
+            self.orbs.update(renderer_interface, dt);
 
             self.simple_physics_simulation.update(renderer_interface);
         }
@@ -451,11 +642,7 @@ impl DefaultApplicationInterfaceRuntime for NeonWarlord {
         watch_index += 1;
         self.watch_fps.start(watch_index, "Update Animations");
         {
-            self.ants.animated_object_storage.update_animations(&dt);
-
-            self.ants
-                .animated_object_storage
-                .update_device_data(renderer_interface);
+            self.ants.update(renderer_interface, &dt);
         }
         self.watch_fps.stop(watch_index);
 
@@ -529,6 +716,15 @@ impl DefaultApplicationInterfaceRuntime for NeonWarlord {
             self.debug_overlay
                 .update_str(renderer_interface, &self.font, 8, &self.id);
 
+            self.debug_overlay
+                .update_str(renderer_interface, &self.font, 9, &self.game_state);
+
+            self.debug_overlay
+                .update_str(renderer_interface, &self.font, 10, &self.kill_feed);
+
+            self.debug_overlay
+                .update_str(renderer_interface, &self.font, 11, &self.wave_progress);
+
             self.performance_monitor_fps.update_from_data(
                 renderer_interface,
                 &self.font,
@@ -571,6 +767,159 @@ impl DefaultApplicationInterfaceRuntime for NeonWarlord {
                 true
             }
             // #########################################################
+            WindowEvent::KeyboardInput {
+                event:
+                    winit::event::KeyEvent {
+                        physical_key:
+                            winit::keyboard::PhysicalKey::Code(winit::keyboard::KeyCode::KeyB),
+                        state: ElementState::Pressed,
+                        ..
+                    },
+                ..
+            } => {
+                // bought units take the next free slot of the formation
+                let _ = self
+                    .worker
+                    .send()
+                    .send(MainMessage::GameCommand(GameCommand::BuyUnit {
+                        unit_type: "ant".to_string(),
+                    }));
+                true
+            }
+            // #########################################################
+            WindowEvent::KeyboardInput {
+                event:
+                    winit::event::KeyEvent {
+                        physical_key: winit::keyboard::PhysicalKey::Code(key_code),
+                        state: ElementState::Pressed,
+                        ..
+                    },
+                ..
+            } if matches!(
+                key_code,
+                winit::keyboard::KeyCode::Digit1
+                    | winit::keyboard::KeyCode::Digit2
+                    | winit::keyboard::KeyCode::Digit3
+                    | winit::keyboard::KeyCode::Digit4
+            ) =>
+            {
+                let formation = match key_code {
+                    winit::keyboard::KeyCode::Digit1 => Formation::Line,
+                    winit::keyboard::KeyCode::Digit2 => Formation::Wedge,
+                    winit::keyboard::KeyCode::Digit3 => Formation::Box,
+                    _ => Formation::Spread,
+                };
+
+                let _ =
+                    self.worker
+                        .send()
+                        .send(MainMessage::GameCommand(GameCommand::SetFormation(
+                            formation,
+                        )));
+                true
+            }
+            // #########################################################
+            WindowEvent::KeyboardInput {
+                event:
+                    winit::event::KeyEvent {
+                        physical_key:
+                            winit::keyboard::PhysicalKey::Code(winit::keyboard::KeyCode::Enter),
+                        state: ElementState::Pressed,
+                        ..
+                    },
+                ..
+            } => {
+                let _ = self
+                    .worker
+                    .send()
+                    .send(MainMessage::GameCommand(GameCommand::StartBattle));
+                true
+            }
+            // #########################################################
+            WindowEvent::KeyboardInput {
+                event:
+                    winit::event::KeyEvent {
+                        physical_key:
+                            winit::keyboard::PhysicalKey::Code(winit::keyboard::KeyCode::F5),
+                        state: ElementState::Pressed,
+                        ..
+                    },
+                ..
+            } => {
+                let _ = self.worker.send().send(MainMessage::SaveGame);
+                true
+            }
+            // #########################################################
+            WindowEvent::KeyboardInput {
+                event:
+                    winit::event::KeyEvent {
+                        physical_key:
+                            winit::keyboard::PhysicalKey::Code(winit::keyboard::KeyCode::F9),
+                        state: ElementState::Pressed,
+                        ..
+                    },
+                ..
+            } => {
+                match SaveGame::read_from_file(SAVE_GAME_PATH) {
+                    Ok(save_game) => {
+                        let _ = self
+                            .worker
+                            .send()
+                            .send(MainMessage::LoadGame(Box::new(save_game)));
+                    }
+                    Err(err) => log::warn!("{}", err),
+                }
+                true
+            }
+            // #########################################################
+            WindowEvent::KeyboardInput {
+                event:
+                    winit::event::KeyEvent {
+                        physical_key:
+                            winit::keyboard::PhysicalKey::Code(winit::keyboard::KeyCode::F6),
+                        state: ElementState::Pressed,
+                        ..
+                    },
+                ..
+            } => {
+                let _ = self.worker.send().send(MainMessage::SaveReplay);
+                true
+            }
+            // #########################################################
+            WindowEvent::KeyboardInput {
+                event:
+                    winit::event::KeyEvent {
+                        physical_key:
+                            winit::keyboard::PhysicalKey::Code(winit::keyboard::KeyCode::F7),
+                        state: ElementState::Pressed,
+                        ..
+                    },
+                ..
+            } => {
+                match self.battle_log.write_to_file(BATTLE_LOG_PATH) {
+                    Ok(()) => log::info!("battle log written to {}", BATTLE_LOG_PATH),
+                    Err(err) => log::warn!("{}", err),
+                }
+                true
+            }
+            // #########################################################
+            WindowEvent::KeyboardInput {
+                event:
+                    winit::event::KeyEvent {
+                        physical_key:
+                            winit::keyboard::PhysicalKey::Code(winit::keyboard::KeyCode::F10),
+                        state: ElementState::Pressed,
+                        ..
+                    },
+                ..
+            } => {
+                match Replay::read_from_file(REPLAY_PATH) {
+                    Ok(replay) => self.play_replay(replay),
+                    Err(err) => log::warn!("{}", err),
+                }
+                true
+            }
+            // #########################################################
             WindowEvent::KeyboardInput {
                 event:
                     winit::event::KeyEvent {
@@ -619,10 +968,17 @@ impl DefaultApplicationInterfaceRuntime for NeonWarlord {
         // render current frame
         let res;
         {
+            let animations: Vec<&dyn AnimationShaderDraw> = self
+                .ants
+                .animated_object_storages
+                .iter()
+                .map(|elem| elem as &dyn AnimationShaderDraw)
+                .collect();
+
             res = self.renderer.render(
                 renderer_interface,
                 &mut self.terrain,
-                &[&self.ants.animated_object_storage],
+                &animations,
                 &[
                     &self.performance_monitor_fps,
                     &self.performance_monitor_ups,
@@ -630,9 +986,9 @@ impl DefaultApplicationInterfaceRuntime for NeonWarlord {
                 ],
                 &[&self.sun, &self.simple_physics_simulation],
                 &[&self.simple_physics_simulation],
-                &[&self.particles],
-                &[&self.plasma_orbs],
-                &[&self.glows],
+                &[&self.orbs.particle_storage],
+                &[&self.orbs.plasma_orb_storage],
+                &[&self.orbs.glow_storage],
                 &mut self.watch_fps,
             )
         }
@@ -650,3 +1006,79 @@ pub fn run() {
 
     default_application::run_app::<NeonWarlord>(event_loop);
 }
+
+/// Runs a battle without a window and prints the outcome
+pub fn run_headless(
+    nr_ants: usize,
+    max_ticks: u64,
+    battle_log_path: Option<&str>,
+) -> Result<(), String> {
+    let settings = settings::Settings::new();
+    let unit_definitions = UnitDefinitions::load_default()?;
+
+    let ant_generator = AntGenerator::new(nr_ants);
+    let scenario = Scenario::from_ant_generator(&ant_generator);
+
+    let mut battle = HeadlessBattle::new(
+        scenario,
+        unit_definitions,
+        settings.get_object_settings().max_nr_orbs,
+    );
+    let result = battle.run(max_ticks);
+
+    println!("{}", result);
+
+    if let Some(path) = battle_log_path {
+        battle.battle_log().write_to_file(path)?;
+    }
+
+    Ok(())
+}
+
+/// Runs many seeded battles between two armies and prints the statistics
+///
+/// The armies are given as "unit_type:count,unit_type:count", the statistics are additionally
+/// written to the output file, as json if it ends with ".json" and as csv otherwise.
+pub fn run_balance(
+    blue_army: &str,
+    red_army: &str,
+    nr_battles: usize,
+    nr_threads: usize,
+    seed: u64,
+    output: Option<&str>,
+) -> Result<(), String> {
+    let settings = settings::Settings::new();
+    let unit_definitions = UnitDefinitions::load_default()?;
+
+    let armies = [
+        Army::parse(Faction::Blue, blue_army)?,
+        Army::parse(Faction::Red, red_army)?,
+    ];
+    for army in &armies {
+        army.validate(&unit_definitions)?;
+    }
+
+    let balance_settings = BalanceSettings {
+        nr_battles,
+        nr_threads,
+        seed,
+        max_ticks: BALANCE_MAX_TICKS,
+        max_nr_orbs: settings.get_object_settings().max_nr_orbs,
+    };
+
+    let report = balance::run_balance(&armies, &unit_definitions, &balance_settings);
+    println!("{}", report);
+
+    if let Some(output) = output {
+        let content = if output.ends_with(".json") {
+            report.to_json()
+        } else {
+            report.to_csv()
+        };
+
+        std::fs::write(output, content)
+            .map_err(|err| format!("could not write '{}': {}", output, err))?;
+    }
+
+    Ok(())
+}
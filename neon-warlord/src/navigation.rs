@@ -0,0 +1,696 @@
+//! Walkability grid over the terrain and A* paths for the ants
+//!
+//! A cell is blocked if the terrain is too steep or too high, the cells are only evaluated when a
+//! search first reaches them.
+//!
+//! Many ants walking to the same area share a flow field instead of searching a path each. The
+//! fields only depend on the target region, so they can be dropped and computed again at any time.
+
+use std::{
+    cmp::Ordering,
+    collections::{BinaryHeap, HashMap},
+};
+
+use cgmath::MetricSpace;
+
+use crate::heightmap_generator::HeightMapGenerator;
+
+type Vec2 = cgmath::Vector2<f32>;
+
+/// Edge length of a cell of the grid
+const CELL_SIZE: f32 = 2.0;
+
+/// Number of cells along x and y, the grid is centered at the origin
+const GRID_SIZE: usize = 256;
+
+/// Steepest rise per unit an ant can climb
+const MAX_SLOPE: f32 = 1.6;
+
+/// Mountain tops above this height are impassable
+const MAX_HEIGHT: f32 = 45.0;
+
+/// Limits the search if the goal is unreachable, the ant walks to the closest cell found so far
+const MAX_SEARCH_NODES: usize = 8192;
+
+/// Distance in cells a start or goal on blocked terrain is moved to reach walkable terrain
+const MAX_SNAP_DISTANCE: usize = 8;
+
+/// Edge length in cells of the regions sharing a flow field, a target leaving it gets a new field
+const FLOW_REGION_SIZE: usize = 4;
+
+/// Cells around the target region covered by a flow field, further away the ants search a path
+const FLOW_FIELD_RADIUS: usize = 48;
+
+/// The least recently used flow field is dropped if there are more
+const MAX_FLOW_FIELDS: usize = 64;
+
+/// Waypoints of a path or cells of a flow field skipped at most by a straight line
+const LOOKAHEAD: usize = 4;
+
+/// Targets closer than this are approached directly if nothing blocks the way
+const DIRECT_DISTANCE: f32 = 16.0;
+
+const NEIGHBOURS: [(isize, isize); 8] = [
+    (1, 0),
+    (-1, 0),
+    (0, 1),
+    (0, -1),
+    (1, 1),
+    (-1, 1),
+    (1, -1),
+    (-1, -1),
+];
+
+#[derive(Clone, Copy, PartialEq)]
+enum Walkability {
+    Unknown,
+    Walkable,
+    Blocked,
+}
+
+/// Entry of the open list, the lowest estimate is popped first
+#[derive(PartialEq)]
+struct OpenNode {
+    estimate: f32,
+    index: usize,
+}
+
+impl Eq for OpenNode {}
+
+impl Ord for OpenNode {
+    fn cmp(&self, other: &Self) -> Ordering {
+        // reversed for the max heap, ties are resolved by the index to stay deterministic
+        other
+            .estimate
+            .total_cmp(&self.estimate)
+            .then_with(|| other.index.cmp(&self.index))
+    }
+}
+
+impl PartialOrd for OpenNode {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+struct SearchNode {
+    cost: f32,
+    parent: usize,
+    is_closed: bool,
+}
+
+/// Cost to walk to the target region from every cell around it
+struct FlowField {
+    region: (usize, usize),
+    min: (usize, usize), // first cell covered by the field
+    size: (usize, usize),
+    costs: Vec<f32>, // infinite if the region can not be reached
+    last_used: u64,
+}
+
+impl FlowField {
+    fn cost(&self, (x, y): (usize, usize)) -> f32 {
+        let (x, y) = (x.wrapping_sub(self.min.0), y.wrapping_sub(self.min.1));
+        if x >= self.size.0 || y >= self.size.1 {
+            return f32::INFINITY;
+        }
+
+        self.costs[y * self.size.0 + x]
+    }
+
+    /// The neighbour closest to the target region, None at the target region or outside the field
+    fn next_cell(&self, cell: (usize, usize)) -> Option<(usize, usize)> {
+        let cost = self.cost(cell);
+        if !cost.is_finite() {
+            return None;
+        }
+
+        let mut best: Option<((usize, usize), f32)> = None;
+        for (dx, dy) in NEIGHBOURS {
+            let (x, y) = (cell.0 as isize + dx, cell.1 as isize + dy);
+            if x < 0 || y < 0 {
+                continue;
+            }
+            let neighbour = (x as usize, y as usize);
+
+            // walkable cells in the field have a finite cost, no cutting of blocked corners
+            if dx != 0 && dy != 0 {
+                let side_x = self.cost((x as usize, cell.1));
+                let side_y = self.cost((cell.0, y as usize));
+                if !side_x.is_finite() || !side_y.is_finite() {
+                    continue;
+                }
+            }
+
+            let neighbour_cost = self.cost(neighbour);
+            if neighbour_cost < best.map_or(cost, |(_, best_cost)| best_cost) {
+                best = Some((neighbour, neighbour_cost));
+            }
+        }
+
+        best.map(|(neighbour, _)| neighbour)
+    }
+}
+
+pub struct NavigationGrid {
+    origin: Vec2,
+    cells: Vec<Walkability>,
+
+    flow_fields: Vec<FlowField>,
+    nr_flow_requests: u64,
+}
+
+impl Default for NavigationGrid {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl NavigationGrid {
+    pub fn new() -> Self {
+        let half_extent = GRID_SIZE as f32 * CELL_SIZE / 2.0;
+        let origin = Vec2::new(-half_extent, -half_extent);
+        let cells = vec![Walkability::Unknown; GRID_SIZE * GRID_SIZE];
+
+        Self {
+            origin,
+            cells,
+            flow_fields: Vec::new(),
+            nr_flow_requests: 0,
+        }
+    }
+
+    fn cell_of(&self, position: Vec2) -> Option<(usize, usize)> {
+        let local = (position - self.origin) / CELL_SIZE;
+        if local.x < 0.0 || local.y < 0.0 {
+            return None;
+        }
+
+        let (x, y) = (local.x as usize, local.y as usize);
+        if x >= GRID_SIZE || y >= GRID_SIZE {
+            return None;
+        }
+
+        Some((x, y))
+    }
+
+    fn cell_center(&self, (x, y): (usize, usize)) -> Vec2 {
+        self.origin + Vec2::new(x as f32 + 0.5, y as f32 + 0.5) * CELL_SIZE
+    }
+
+    fn is_walkable(&mut self, terrain: &HeightMapGenerator, (x, y): (usize, usize)) -> bool {
+        let index = y * GRID_SIZE + x;
+
+        if self.cells[index] == Walkability::Unknown {
+            let corner = self.origin + Vec2::new(x as f32, y as f32) * CELL_SIZE;
+            let heights = [
+                terrain.height(corner),
+                terrain.height(corner + Vec2::new(CELL_SIZE, 0.0)),
+                terrain.height(corner + Vec2::new(0.0, CELL_SIZE)),
+                terrain.height(corner + Vec2::new(CELL_SIZE, CELL_SIZE)),
+            ];
+
+            let rise = (heights[1] - heights[0])
+                .abs()
+                .max((heights[2] - heights[0]).abs())
+                .max((heights[3] - heights[1]).abs())
+                .max((heights[3] - heights[2]).abs());
+            let height = heights.iter().fold(f32::MIN, |a, b| a.max(*b));
+
+            self.cells[index] = if rise / CELL_SIZE <= MAX_SLOPE && height <= MAX_HEIGHT {
+                Walkability::Walkable
+            } else {
+                Walkability::Blocked
+            };
+        }
+
+        self.cells[index] == Walkability::Walkable
+    }
+
+    /// The closest walkable cell, searched in rings around the cell
+    fn nearest_walkable(
+        &mut self,
+        terrain: &HeightMapGenerator,
+        (x, y): (usize, usize),
+    ) -> Option<(usize, usize)> {
+        for ring in 0..=MAX_SNAP_DISTANCE as isize {
+            let mut closest: Option<(isize, (usize, usize))> = None;
+
+            for dy in -ring..=ring {
+                for dx in -ring..=ring {
+                    if dx.abs() != ring && dy.abs() != ring {
+                        continue;
+                    }
+
+                    let (cx, cy) = (x as isize + dx, y as isize + dy);
+                    if cx < 0 || cy < 0 || cx >= GRID_SIZE as isize || cy >= GRID_SIZE as isize {
+                        continue;
+                    }
+                    let cell = (cx as usize, cy as usize);
+
+                    let distance = dx * dx + dy * dy;
+                    if closest.is_none_or(|(closest_distance, _)| distance < closest_distance)
+                        && self.is_walkable(terrain, cell)
+                    {
+                        closest = Some((distance, cell));
+                    }
+                }
+            }
+
+            if let Some((_, cell)) = closest {
+                return Some(cell);
+            }
+        }
+
+        None
+    }
+
+    /// Checks the cells along the line, the cell the line starts in is skipped
+    fn line_of_sight(&mut self, terrain: &HeightMapGenerator, from: Vec2, to: Vec2) -> bool {
+        let start_cell = self.cell_of(from);
+        let nr_steps = (from.distance(to) / (CELL_SIZE * 0.25)).ceil() as usize;
+
+        for step in 1..=nr_steps {
+            let position = from + (to - from) * (step as f32 / nr_steps as f32);
+            match self.cell_of(position) {
+                Some(cell) if Some(cell) == start_cell => {}
+                Some(cell) => {
+                    if !self.is_walkable(terrain, cell) {
+                        return false;
+                    }
+                }
+                None => return false,
+            }
+        }
+
+        true
+    }
+
+    /// How far along the line the ant gets before blocked terrain or the end of the grid
+    pub fn walkable_end(&mut self, terrain: &HeightMapGenerator, from: Vec2, to: Vec2) -> Vec2 {
+        let start_cell = self.cell_of(from);
+        let nr_steps = (from.distance(to) / (CELL_SIZE * 0.25)).ceil() as usize;
+
+        let mut end = from;
+        for step in 1..=nr_steps {
+            let position = from + (to - from) * (step as f32 / nr_steps as f32);
+            match self.cell_of(position) {
+                Some(cell) if Some(cell) == start_cell => {}
+                Some(cell) if self.is_walkable(terrain, cell) => {}
+                _ => break,
+            }
+            end = position;
+        }
+
+        end
+    }
+
+    /// The position itself if it is walkable, the center of the closest walkable cell otherwise
+    pub fn nearest_walkable_position(
+        &mut self,
+        terrain: &HeightMapGenerator,
+        position: Vec2,
+    ) -> Option<Vec2> {
+        let cell = self.cell_of(position)?;
+        if self.is_walkable(terrain, cell) {
+            return Some(position);
+        }
+
+        self.nearest_walkable(terrain, cell)
+            .map(|cell| self.cell_center(cell))
+    }
+
+    /// Waypoints from the start to the goal, or to the closest reachable point instead
+    pub fn find_path(
+        &mut self,
+        terrain: &HeightMapGenerator,
+        start: Vec2,
+        goal: Vec2,
+    ) -> Vec<Vec2> {
+        // outside of the grid the ants walk in a straight line
+        let (Some(start_cell), Some(goal_cell)) = (self.cell_of(start), self.cell_of(goal)) else {
+            return vec![goal];
+        };
+        if start_cell == goal_cell {
+            return vec![goal];
+        }
+
+        // steep terrain is left and approached on the shortest way
+        let (Some(start_cell), Some(goal_cell)) = (
+            self.nearest_walkable(terrain, start_cell),
+            self.nearest_walkable(terrain, goal_cell),
+        ) else {
+            return vec![goal];
+        };
+        let is_goal_walkable = self.cell_of(goal) == Some(goal_cell);
+
+        let index_of = |(x, y): (usize, usize)| y * GRID_SIZE + x;
+        let cell_of_index = |index: usize| (index % GRID_SIZE, index / GRID_SIZE);
+        let heuristic = |(x, y): (usize, usize)| {
+            let dx = x.abs_diff(goal_cell.0) as f32;
+            let dy = y.abs_diff(goal_cell.1) as f32;
+            (dx.max(dy) + (std::f32::consts::SQRT_2 - 1.0) * dx.min(dy)) * CELL_SIZE
+        };
+
+        let start_index = index_of(start_cell);
+        let goal_index = index_of(goal_cell);
+
+        let mut open = BinaryHeap::new();
+        let mut nodes: HashMap<usize, SearchNode> = HashMap::new();
+        open.push(OpenNode {
+            estimate: heuristic(start_cell),
+            index: start_index,
+        });
+        nodes.insert(
+            start_index,
+            SearchNode {
+                cost: 0.0,
+                parent: start_index,
+                is_closed: false,
+            },
+        );
+
+        let mut closest = (heuristic(start_cell), start_index);
+        let mut nr_expanded = 0;
+        while let Some(OpenNode { index, .. }) = open.pop() {
+            let node = nodes.get_mut(&index).expect("open nodes are known");
+            if node.is_closed {
+                continue;
+            }
+            node.is_closed = true;
+            let cost = node.cost;
+
+            let cell = cell_of_index(index);
+            let distance = heuristic(cell);
+            if distance < closest.0 {
+                closest = (distance, index);
+            }
+            if index == goal_index {
+                break;
+            }
+
+            nr_expanded += 1;
+            if nr_expanded >= MAX_SEARCH_NODES {
+                break;
+            }
+
+            for (dx, dy) in NEIGHBOURS {
+                let (x, y) = (cell.0 as isize + dx, cell.1 as isize + dy);
+                if x < 0 || y < 0 || x >= GRID_SIZE as isize || y >= GRID_SIZE as isize {
+                    continue;
+                }
+                let neighbour = (x as usize, y as usize);
+                if !self.is_walkable(terrain, neighbour) {
+                    continue;
+                }
+
+                // no cutting of blocked corners
+                let is_diagonal = dx != 0 && dy != 0;
+                if is_diagonal
+                    && (!self.is_walkable(terrain, (x as usize, cell.1))
+                        || !self.is_walkable(terrain, (cell.0, y as usize)))
+                {
+                    continue;
+                }
+
+                let step = if is_diagonal {
+                    CELL_SIZE * std::f32::consts::SQRT_2
+                } else {
+                    CELL_SIZE
+                };
+                let neighbour_cost = cost + step;
+                let neighbour_index = index_of(neighbour);
+
+                let is_better = nodes
+                    .get(&neighbour_index)
+                    .is_none_or(|node| !node.is_closed && neighbour_cost < node.cost);
+                if is_better {
+                    nodes.insert(
+                        neighbour_index,
+                        SearchNode {
+                            cost: neighbour_cost,
+                            parent: index,
+                            is_closed: false,
+                        },
+                    );
+                    open.push(OpenNode {
+                        estimate: neighbour_cost + heuristic(neighbour),
+                        index: neighbour_index,
+                    });
+                }
+            }
+        }
+
+        // cells from the start to the closest cell
+        let mut cells = Vec::new();
+        let mut index = closest.1;
+        while index != start_index {
+            cells.push(index);
+            index = nodes[&index].parent;
+        }
+        cells.reverse();
+
+        let mut points = vec![start];
+        if self.cell_of(start) != Some(start_cell) {
+            points.push(self.cell_center(start_cell));
+        }
+        points.extend(
+            cells
+                .iter()
+                .map(|index| self.cell_center(cell_of_index(*index))),
+        );
+        if closest.1 == goal_index && is_goal_walkable {
+            // the goal itself instead of the center of its cell
+            if !cells.is_empty() {
+                points.pop();
+            }
+            points.push(goal);
+        }
+
+        self.smooth(terrain, &points)
+    }
+
+    /// The next point on the way to the target, None if no flow field covers the position
+    pub fn flow_waypoint(
+        &mut self,
+        terrain: &HeightMapGenerator,
+        position: Vec2,
+        target: Vec2,
+    ) -> Option<Vec2> {
+        let cell = self.cell_of(position)?;
+
+        // close to the target it is approached directly
+        if position.distance2(target) <= DIRECT_DISTANCE * DIRECT_DISTANCE
+            && self.line_of_sight(terrain, position, target)
+        {
+            return Some(target);
+        }
+
+        let index = self.flow_field(terrain, target)?;
+        let field = &self.flow_fields[index];
+        let first = field.next_cell(cell)?;
+        let mut ahead = first;
+        for _ in 1..LOOKAHEAD {
+            match field.next_cell(ahead) {
+                Some(next) => ahead = next,
+                None => break,
+            }
+        }
+
+        // cut across the steps of the grid where nothing blocks the way
+        let ahead = self.cell_center(ahead);
+        if self.line_of_sight(terrain, position, ahead) {
+            Some(ahead)
+        } else {
+            Some(self.cell_center(first))
+        }
+    }
+
+    /// Index of the flow field leading to the target, computed if it is not cached
+    fn flow_field(&mut self, terrain: &HeightMapGenerator, target: Vec2) -> Option<usize> {
+        let target_cell = self.cell_of(target)?;
+        let region = (
+            target_cell.0 / FLOW_REGION_SIZE,
+            target_cell.1 / FLOW_REGION_SIZE,
+        );
+
+        self.nr_flow_requests += 1;
+        if let Some(index) = self
+            .flow_fields
+            .iter()
+            .position(|field| field.region == region)
+        {
+            self.flow_fields[index].last_used = self.nr_flow_requests;
+            return Some(index);
+        }
+
+        let field = self.compute_flow_field(terrain, region)?;
+        if self.flow_fields.len() < MAX_FLOW_FIELDS {
+            self.flow_fields.push(field);
+            return Some(self.flow_fields.len() - 1);
+        }
+
+        let (index, _) = self
+            .flow_fields
+            .iter()
+            .enumerate()
+            .min_by_key(|(_, field)| field.last_used)
+            .expect("the cache is full");
+        self.flow_fields[index] = field;
+        Some(index)
+    }
+
+    /// Dijkstra from the center of the region over all cells of the window around it
+    fn compute_flow_field(
+        &mut self,
+        terrain: &HeightMapGenerator,
+        region: (usize, usize),
+    ) -> Option<FlowField> {
+        let center = (
+            (region.0 * FLOW_REGION_SIZE + FLOW_REGION_SIZE / 2).min(GRID_SIZE - 1),
+            (region.1 * FLOW_REGION_SIZE + FLOW_REGION_SIZE / 2).min(GRID_SIZE - 1),
+        );
+        let source = self.nearest_walkable(terrain, center)?;
+
+        let min = (
+            center.0.saturating_sub(FLOW_FIELD_RADIUS),
+            center.1.saturating_sub(FLOW_FIELD_RADIUS),
+        );
+        let max = (
+            (center.0 + FLOW_FIELD_RADIUS).min(GRID_SIZE - 1),
+            (center.1 + FLOW_FIELD_RADIUS).min(GRID_SIZE - 1),
+        );
+        let size = (max.0 - min.0 + 1, max.1 - min.1 + 1);
+
+        let mut field = FlowField {
+            region,
+            min,
+            size,
+            costs: vec![f32::INFINITY; size.0 * size.1],
+            last_used: self.nr_flow_requests,
+        };
+        let local_index = |(x, y): (usize, usize)| (y - min.1) * size.0 + (x - min.0);
+
+        let mut open = BinaryHeap::new();
+        field.costs[local_index(source)] = 0.0;
+        open.push(OpenNode {
+            estimate: 0.0,
+            index: source.1 * GRID_SIZE + source.0,
+        });
+
+        while let Some(OpenNode { estimate, index }) = open.pop() {
+            let cell = (index % GRID_SIZE, index / GRID_SIZE);
+            if estimate > field.costs[local_index(cell)] {
+                continue;
+            }
+
+            for (dx, dy) in NEIGHBOURS {
+                let (x, y) = (cell.0 as isize + dx, cell.1 as isize + dy);
+                if x < min.0 as isize
+                    || y < min.1 as isize
+                    || x > max.0 as isize
+                    || y > max.1 as isize
+                {
+                    continue;
+                }
+                let neighbour = (x as usize, y as usize);
+                if !self.is_walkable(terrain, neighbour) {
+                    continue;
+                }
+
+                let is_diagonal = dx != 0 && dy != 0;
+                if is_diagonal
+                    && (!self.is_walkable(terrain, (x as usize, cell.1))
+                        || !self.is_walkable(terrain, (cell.0, y as usize)))
+                {
+                    continue;
+                }
+
+                let step = if is_diagonal {
+                    CELL_SIZE * std::f32::consts::SQRT_2
+                } else {
+                    CELL_SIZE
+                };
+                let cost = estimate + step;
+                if cost < field.costs[local_index(neighbour)] {
+                    field.costs[local_index(neighbour)] = cost;
+                    open.push(OpenNode {
+                        estimate: cost,
+                        index: neighbour.1 * GRID_SIZE + neighbour.0,
+                    });
+                }
+            }
+        }
+
+        Some(field)
+    }
+
+    /// Skips all points which can be reached in a straight line
+    fn smooth(&mut self, terrain: &HeightMapGenerator, points: &[Vec2]) -> Vec<Vec2> {
+        let mut path = Vec::new();
+
+        let mut anchor = points[0];
+        let mut next = 1;
+        while next < points.len() {
+            let mut furthest = next;
+            while furthest + 1 < points.len()
+                && furthest + 1 - next < LOOKAHEAD
+                && self.line_of_sight(terrain, anchor, points[furthest + 1])
+            {
+                furthest += 1;
+            }
+
+            path.push(points[furthest]);
+            anchor = points[furthest];
+            next = furthest + 1;
+        }
+
+        path
+    }
+}
+
+#[test]
+fn test_paths_lead_around_a_wall() {
+    let terrain = HeightMapGenerator::new();
+    let mut grid = NavigationGrid::new();
+
+    // the terrain is flat far away from the canyon, a wall with a gap at its upper end
+    let wall_x = grid.cell_of(Vec2::new(-150.0, -150.0)).unwrap().0;
+    let gap_y = grid.cell_of(Vec2::new(-150.0, -120.0)).unwrap().1;
+    for y in 0..gap_y {
+        grid.cells[y * GRID_SIZE + wall_x] = Walkability::Blocked;
+    }
+
+    let start = Vec2::new(-170.0, -200.0);
+    let goal = Vec2::new(-130.0, -200.0);
+    let path = grid.find_path(&terrain, start, goal);
+
+    assert_eq!(*path.last().unwrap(), goal);
+    assert!(path.iter().any(|point| point.y >= -120.0));
+
+    // every segment is free of blocked cells
+    let mut from = start;
+    for point in &path {
+        assert!(grid.line_of_sight(&terrain, from, *point));
+        from = *point;
+    }
+
+    // the flow field leads through the gap as well
+    let mut position = start;
+    for _ in 0..200 {
+        let Some(waypoint) = grid.flow_waypoint(&terrain, position, goal) else {
+            break;
+        };
+        assert!(grid.line_of_sight(&terrain, position, waypoint));
+        position = waypoint;
+    }
+    assert_eq!(position, goal);
+
+    // a straight jump through the wall ends in front of it
+    let end = grid.walkable_end(&terrain, start, goal);
+    assert!(end.x < -150.0 && end.x > -160.0);
+    assert_eq!(
+        grid.walkable_end(&terrain, goal, goal + Vec2::unit_x()),
+        goal + Vec2::unit_x()
+    );
+}
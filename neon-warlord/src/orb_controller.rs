@@ -1,15 +1,12 @@
-//! Implements the WorldInterface from AntAi
-
-use std::time::Instant;
+//! Routines controlling a plasma orb
 
 use cgmath::InnerSpace;
+use serde::{Deserialize, Serialize};
+
+use crate::unit_definition::{self, ProjectileDefinition};
 
-#[allow(unused)]
-type Vec2 = cgmath::Vector2<f32>;
-#[allow(unused)]
 type Vec3 = cgmath::Vector3<f32>;
 
-#[allow(unused)]
 #[derive(Clone, Copy)]
 pub enum OrbAction {
     SetPosition(Vec3),
@@ -17,8 +14,13 @@ pub enum OrbAction {
     SetScale(f32),
 }
 
-#[allow(unused)]
-#[derive(PartialEq)]
+#[derive(Clone, Copy)]
+pub struct OrbActionStruct {
+    pub action: OrbAction,
+    pub index: usize,
+}
+
+#[derive(Clone, Copy, PartialEq, Serialize, Deserialize)]
 enum State {
     Idle,
     StartCharge,
@@ -29,7 +31,7 @@ enum State {
     Explode,
 }
 
-#[allow(unused)]
+#[derive(Clone, Serialize, Deserialize)]
 pub struct OrbController {
     position: Vec3,
     target_position: Vec3,
@@ -37,32 +39,57 @@ pub struct OrbController {
     state: State,
 
     index: usize,
+    #[serde(skip)] // a loaded orb is shown again on the render side
+    is_synced: bool,
+
+    // Projectile
+    charge_duration: u64,
+    speed: f32,
+    max_scale: f32,
 
     // State::Charge
-    start_charge_time: Instant,
+    start_charge_tick: u64,
     charge: f32,
 }
 
-#[allow(unused)]
 impl OrbController {
     pub fn new(position: Vec3, index: usize) -> Self {
         let target_position = position;
-        let state = State::Charge;
+        let state = State::Idle;
 
         Self {
             position,
             target_position,
             state,
             index,
-            start_charge_time: Instant::now(),
+            is_synced: false,
+            charge_duration: 0,
+            speed: 0.0,
+            max_scale: 0.0,
+            start_charge_tick: 0,
             charge: 0.0,
         }
     }
 
-    pub fn update(&mut self, time_stamp: &Instant, actions: &mut Vec<OrbAction>) {
-        const CHARGE_DURATION: f32 = 5.0;
-        const SPEED: f32 = 0.01;
-        const MAX_SCALE: f32 = 0.1;
+    /// Takes over the parameters of the projectile type the orb is fired as
+    pub fn set_projectile(&mut self, projectile: &ProjectileDefinition, charge_duration: u64) {
+        self.charge_duration = charge_duration;
+        self.speed = unit_definition::per_second_to_per_tick(projectile.speed);
+        self.max_scale = projectile.max_scale;
+    }
+
+    pub fn update(&mut self, tick: u64, actions: &mut Vec<OrbActionStruct>) {
+        // send the whole state on the first update
+        if !self.is_synced {
+            self.push(OrbAction::SetPosition(self.position), actions);
+            self.push(OrbAction::SetScale(self.charge), actions);
+            self.push(
+                OrbAction::EnableCharge(self.state == State::Charge),
+                actions,
+            );
+
+            self.is_synced = true;
+        }
 
         match self.state {
             // ##################################################
@@ -71,24 +98,24 @@ impl OrbController {
             }
             // ##################################################
             State::StartCharge => {
-                actions.push(OrbAction::SetPosition(self.position));
-                actions.push(OrbAction::SetScale(0.01));
-                actions.push(OrbAction::EnableCharge(true));
+                self.push(OrbAction::SetPosition(self.position), actions);
+                self.push(OrbAction::SetScale(0.01), actions);
+                self.push(OrbAction::EnableCharge(true), actions);
 
-                self.start_charge_time = *time_stamp;
+                self.start_charge_tick = tick;
                 self.state = State::Charge;
             }
             // ##################################################
             State::Charge => {
-                let duration = *time_stamp - self.start_charge_time;
-                let t = duration.as_secs_f32();
+                let duration = tick - self.start_charge_tick;
 
-                let scale = (t / CHARGE_DURATION) * MAX_SCALE;
+                let progress = duration as f32 / self.charge_duration.max(1) as f32;
+                let scale = progress.min(1.0) * self.max_scale;
                 self.charge = scale;
-                actions.push(OrbAction::SetScale(scale));
+                self.push(OrbAction::SetScale(scale), actions);
 
-                if t >= CHARGE_DURATION {
-                    actions.push(OrbAction::EnableCharge(false));
+                if duration >= self.charge_duration {
+                    self.push(OrbAction::EnableCharge(false), actions);
                     self.state = State::ChargingDone;
                 }
             }
@@ -104,35 +131,54 @@ impl OrbController {
             State::Move => {
                 // has the position been reached
                 let finish_reached =
-                    (self.target_position - self.position).magnitude2() <= SPEED * SPEED;
+                    (self.target_position - self.position).magnitude2() <= self.speed * self.speed;
 
                 // calculate next step
-                self.position =
-                    self.position + (self.target_position - self.position).normalize() * SPEED;
-
                 if finish_reached {
                     self.position = self.target_position;
                     self.state = State::Explode;
+                } else {
+                    self.position +=
+                        (self.target_position - self.position).normalize() * self.speed;
                 }
+
+                self.push(OrbAction::SetPosition(self.position), actions);
             }
             // ##################################################
             State::Explode => {
+                self.push(OrbAction::SetScale(0.0), actions);
+
                 self.charge = 0.0;
                 self.state = State::Idle;
             }
         }
     }
+
+    fn push(&self, action: OrbAction, actions: &mut Vec<OrbActionStruct>) {
+        actions.push(OrbActionStruct {
+            action,
+            index: self.index,
+        });
+    }
 }
 
-#[allow(unused)]
-trait OrbInterface {
+pub trait OrbInterface {
+    fn get_position(&self) -> Vec3;
     fn set_position(&mut self, pos: Vec3);
     fn start_charge(&mut self);
+    #[allow(unused)]
     fn get_charge(&self) -> f32;
     fn move_to(&mut self, target_position: Vec3);
+    fn is_idle(&self) -> bool;
+    fn is_charged(&self) -> bool;
+    fn is_exploding(&self) -> bool;
 }
 
 impl OrbInterface for OrbController {
+    fn get_position(&self) -> Vec3 {
+        self.position
+    }
+
     fn set_position(&mut self, pos: Vec3) {
         self.position = pos;
     }
@@ -153,4 +199,16 @@ impl OrbInterface for OrbController {
             self.state = State::StartMove;
         }
     }
+
+    fn is_idle(&self) -> bool {
+        self.state == State::Idle
+    }
+
+    fn is_charged(&self) -> bool {
+        self.state == State::ChargingDone
+    }
+
+    fn is_exploding(&self) -> bool {
+        self.state == State::Explode
+    }
 }
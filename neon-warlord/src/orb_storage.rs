@@ -5,17 +5,17 @@ use forward_renderer::{
     plasma_orb_storage::PlasmaOrbStorage,
 };
 
-#[allow(unused)]
 pub struct OrbStorage {
-    particle_storage: ParticleStorage,
-    glow_storage: GlowStorage,
-    plasma_orb_storage: PlasmaOrbStorage,
+    pub particle_storage: ParticleStorage,
+    pub glow_storage: GlowStorage,
+    pub plasma_orb_storage: PlasmaOrbStorage,
 
-    max_instances: usize,
+    _max_instances: usize,
 }
 
-#[allow(unused)]
 impl OrbStorage {
+    const CHARGE_SIZE: f32 = 0.1;
+
     pub fn new(
         wgpu_renderer: &mut dyn wgpu_renderer::wgpu_renderer::WgpuRendererInterface,
         max_instances: usize,
@@ -24,12 +24,20 @@ impl OrbStorage {
         let glow_storage = GlowStorage::new(wgpu_renderer, max_instances);
         let plasma_orb_storage = PlasmaOrbStorage::new(wgpu_renderer, max_instances);
 
-        Self {
+        let mut orb_storage = Self {
             particle_storage,
             glow_storage,
             plasma_orb_storage,
-            max_instances,
+            _max_instances: max_instances,
+        };
+
+        // orbs are hidden until they get charged
+        for i in 0..max_instances {
+            orb_storage.set_size(i, 0.0);
+            orb_storage.enable_charge(i, false);
         }
+
+        orb_storage
     }
 
     pub fn set_position(&mut self, index: usize, pos: cgmath::Vector3<f32>) {
@@ -43,4 +51,20 @@ impl OrbStorage {
         self.glow_storage.set_size(index, size);
         self.plasma_orb_storage.set_size(index, size);
     }
+
+    /// Shows the particles sucked into a charging orb
+    pub fn enable_charge(&mut self, index: usize, enable: bool) {
+        let size = if enable { Self::CHARGE_SIZE } else { 0.0 };
+        self.particle_storage.set_size(index, size);
+    }
+
+    pub fn update(
+        &mut self,
+        wgpu_renderer: &mut dyn wgpu_renderer::wgpu_renderer::WgpuRendererInterface,
+        dt: instant::Duration,
+    ) {
+        self.particle_storage.update(wgpu_renderer, dt);
+        self.glow_storage.update(wgpu_renderer, dt);
+        self.plasma_orb_storage.update(wgpu_renderer, dt);
+    }
 }
@@ -0,0 +1,242 @@
+//! Records the inputs of the worker to play a game back exactly as it happened
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    game_flow::GameCommand, save_game::SaveGame, scenario::Scenario,
+    unit_definition::UnitDefinitions, wave::WaveScript,
+};
+
+/// Increased whenever the format of the replay changes
+pub const REPLAY_VERSION: u32 = 10;
+
+/// A message of the main thread which changes the simulation
+#[derive(Clone, Serialize, Deserialize)]
+pub enum ReplayInput {
+    GameCommand(GameCommand),
+    LoadGame(Box<SaveGame>),
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct ReplayEvent {
+    pub tick: u64, // tick of the clock the input has been consumed at
+    pub input: ReplayInput,
+}
+
+/// Everything needed to run the same game again, the simulation itself is deterministic
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Replay {
+    pub version: u32,
+    pub length: u64, // number of recorded ticks
+
+    // Setup of the worker
+    pub scenario: Scenario,
+    pub unit_definitions: UnitDefinitions,
+    pub wave_script: Option<WaveScript>, // only in the PvE mode
+    pub terrain_seed: u32,
+    pub max_nr_ants: usize,
+    pub max_nr_orbs: usize,
+
+    pub events: Vec<ReplayEvent>,
+}
+
+/// Only the version, read first to reject incompatible files with a clear message
+#[derive(Deserialize)]
+struct ReplayHeader {
+    version: u32,
+}
+
+impl Replay {
+    pub fn new(
+        scenario: Scenario,
+        unit_definitions: UnitDefinitions,
+        wave_script: Option<WaveScript>,
+        terrain_seed: u32,
+        max_nr_ants: usize,
+        max_nr_orbs: usize,
+    ) -> Self {
+        Self {
+            version: REPLAY_VERSION,
+            length: 0,
+            scenario,
+            unit_definitions,
+            wave_script,
+            terrain_seed,
+            max_nr_ants,
+            max_nr_orbs,
+            events: Vec::new(),
+        }
+    }
+
+    pub fn record(&mut self, tick: u64, input: ReplayInput) {
+        self.events.push(ReplayEvent { tick, input });
+    }
+
+    /// The replay of the ticks before length
+    pub fn until(&self, length: u64) -> Self {
+        let events = self
+            .events
+            .iter()
+            .filter(|event| event.tick < length)
+            .cloned()
+            .collect();
+
+        Self {
+            version: self.version,
+            length,
+            scenario: self.scenario.clone(),
+            unit_definitions: self.unit_definitions.clone(),
+            wave_script: self.wave_script.clone(),
+            terrain_seed: self.terrain_seed,
+            max_nr_ants: self.max_nr_ants,
+            max_nr_orbs: self.max_nr_orbs,
+            events,
+        }
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).expect("replay is always serializable")
+    }
+
+    pub fn from_json(json: &str) -> Result<Self, String> {
+        let header: ReplayHeader =
+            serde_json::from_str(json).map_err(|err| format!("replay: {}", err))?;
+        if header.version != REPLAY_VERSION {
+            return Err(format!(
+                "replay: unsupported version {}, expected {}",
+                header.version, REPLAY_VERSION
+            ));
+        }
+
+        let replay: Self = serde_json::from_str(json).map_err(|err| format!("replay: {}", err))?;
+
+        // the worker expects valid unit types
+        replay.unit_definitions.validate()?;
+        for unit in &replay.scenario.units {
+            replay.unit_definitions.index_of(&unit.unit_type)?;
+        }
+
+        Ok(replay)
+    }
+
+    /// Files are only available on desktop, in the browser this returns an error
+    pub fn write_to_file(&self, path: &str) -> Result<(), String> {
+        std::fs::write(path, self.to_json()).map_err(|err| format!("{}: {}", path, err))
+    }
+
+    pub fn read_from_file(path: &str) -> Result<Self, String> {
+        let json = std::fs::read_to_string(path).map_err(|err| format!("{}: {}", path, err))?;
+
+        Self::from_json(&json)
+    }
+}
+
+/// Hands out the recorded inputs tick by tick
+pub struct ReplayPlayback {
+    replay: Replay,
+    next_event: usize,
+}
+
+impl ReplayPlayback {
+    pub fn new(replay: Replay) -> Self {
+        Self {
+            replay,
+            next_event: 0,
+        }
+    }
+
+    pub fn is_finished(&self, tick: u64) -> bool {
+        tick >= self.replay.length
+    }
+
+    /// All inputs recorded at the tick, in the order they have been consumed
+    pub fn take_inputs(&mut self, tick: u64) -> Vec<ReplayInput> {
+        let mut inputs = Vec::new();
+
+        while let Some(event) = self.replay.events.get(self.next_event) {
+            if event.tick > tick {
+                break;
+            }
+
+            inputs.push(event.input.clone());
+            self.next_event += 1;
+        }
+
+        inputs
+    }
+}
+
+#[test]
+fn test_playback_reproduces_the_game() {
+    use std::sync::mpsc;
+
+    use crate::{
+        ant_generator::AntGenerator,
+        worker::{MainMessage, Worker, WorkerMessage},
+    };
+
+    let ant_generator = AntGenerator::new(16);
+    let scenario = Scenario::from_ant_generator(&ant_generator);
+    let unit_definitions = UnitDefinitions::load_default().unwrap();
+
+    // play a round with a bought unit
+    let (channel_0_tx, channel_0_rx) = mpsc::channel();
+    let (channel_1_tx, channel_1_rx) = mpsc::channel();
+    let mut worker_0 = Worker::new(
+        channel_0_rx,
+        channel_1_tx,
+        scenario,
+        unit_definitions,
+        17,
+        16,
+    );
+    for tick in 0..900 {
+        let message = match tick {
+            10 => Some(GameCommand::BuyUnit {
+                unit_type: "ant".to_string(),
+            }),
+            20 => Some(GameCommand::StartBattle),
+            _ => None,
+        };
+        if let Some(game_command) = message {
+            channel_0_tx
+                .send(MainMessage::GameCommand(game_command))
+                .unwrap();
+        }
+
+        worker_0.update(tick);
+    }
+
+    channel_0_tx.send(MainMessage::SaveReplay).unwrap();
+    worker_0.update(900);
+    let replay = channel_1_rx
+        .try_iter()
+        .find_map(|message| match message {
+            WorkerMessage::ReplaySaved(replay) => Some(replay),
+            _ => None,
+        })
+        .unwrap();
+    let replay = Replay::from_json(&replay.to_json()).unwrap();
+    assert_eq!(replay.length, 900);
+    assert_eq!(replay.events.len(), 2);
+
+    // play it back with live input being ignored
+    let (channel_0_tx, channel_0_rx) = mpsc::channel();
+    let (channel_1_tx, _channel_1_rx) = mpsc::channel();
+    let mut worker_1 = Worker::from_replay(channel_0_rx, channel_1_tx, replay);
+    channel_0_tx
+        .send(MainMessage::GameCommand(GameCommand::StartBattle))
+        .unwrap();
+    for tick in 0..=900 {
+        worker_1.update(tick);
+    }
+
+    let result_0 = worker_0.get_battle_result(900);
+    let result_1 = worker_1.get_battle_result(900);
+    assert_eq!(result_0.units.len(), 17);
+    assert_eq!(result_0.units.len(), result_1.units.len());
+    for (unit_0, unit_1) in result_0.units.iter().zip(&result_1.units) {
+        assert_eq!(unit_0.health, unit_1.health);
+        assert_eq!(unit_0.damage_dealt, unit_1.damage_dealt);
+    }
+}
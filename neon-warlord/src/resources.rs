@@ -0,0 +1,70 @@
+//! Files of the res folder, read when they are needed so designers can change them without
+//! rebuilding the game
+//!
+//! The folder is looked up in the working directory, NEON_WARLORD_RES points to another one.
+
+use std::borrow::Cow;
+
+/// Environment variable overriding the folder of the files
+#[cfg(not(target_arch = "wasm32"))]
+const RES_DIR_VARIABLE: &str = "NEON_WARLORD_RES";
+
+/// Folder of the files relative to the working directory
+#[cfg(not(target_arch = "wasm32"))]
+const RES_DIR: &str = "res";
+
+/// Files embedded for the browser, which has no file system to load them from
+#[cfg(target_arch = "wasm32")]
+const FILES: [(&str, &[u8]); 3] = [
+    ("units.json", include_bytes!("../res/units.json")),
+    ("waves.json", include_bytes!("../res/waves.json")),
+    ("ant_0_10.glb", include_bytes!("../res/ant_0_10.glb")),
+];
+
+/// Reads the file from the res folder
+#[cfg(not(target_arch = "wasm32"))]
+pub fn read(name: &str) -> Result<Cow<'static, [u8]>, String> {
+    let dir = std::env::var_os(RES_DIR_VARIABLE).unwrap_or_else(|| RES_DIR.into());
+    let path = std::path::Path::new(&dir).join(name);
+
+    std::fs::read(&path).map(Cow::Owned).map_err(|err| {
+        format!(
+            "could not read '{}': {}, set {} to the res folder",
+            path.display(),
+            err,
+            RES_DIR_VARIABLE
+        )
+    })
+}
+
+/// Takes the file from the embedded ones
+#[cfg(target_arch = "wasm32")]
+pub fn read(name: &str) -> Result<Cow<'static, [u8]>, String> {
+    FILES
+        .iter()
+        .find(|(file, _)| *file == name)
+        .map(|(_, bytes)| Cow::Borrowed(*bytes))
+        .ok_or_else(|| {
+            let names: Vec<&str> = FILES.iter().map(|(file, _)| *file).collect();
+            format!(
+                "'{}' is not embedded, expected one of: {}",
+                name,
+                names.join(", ")
+            )
+        })
+}
+
+/// Reads the text file from the res folder
+pub fn read_to_string(name: &str) -> Result<String, String> {
+    let bytes = read(name)?;
+
+    String::from_utf8(bytes.into_owned()).map_err(|err| format!("{}: {}", name, err))
+}
+
+#[test]
+fn test_files_are_read_from_res() {
+    assert!(read_to_string("units.json").unwrap().contains("\"units\""));
+
+    let err = read("missing.glb").unwrap_err();
+    assert!(err.contains("missing.glb"), "{}", err);
+}
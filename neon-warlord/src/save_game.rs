@@ -0,0 +1,168 @@
+//! Versioned snapshot of the whole simulation state of the worker
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    ant_unit::AntUnit, combat::Combat, game_board::GameBoard, game_flow::GameFlow,
+    wave::WaveDirector,
+};
+
+/// Increased whenever the format of the save game changes
+pub const SAVE_GAME_VERSION: u32 = 8;
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct SaveGame {
+    pub version: u32,
+    pub tick: u64, // the simulation continues with this tick after loading
+
+    // Terrain
+    pub terrain_seed: u32,
+
+    // Game board
+    pub game_board: GameBoard,
+
+    // Game flow
+    pub game_flow: GameFlow,
+
+    // Ants
+    pub ants: Vec<AntUnit>,
+
+    // Combat
+    pub combat: Combat,
+
+    // Waves
+    pub waves: Option<WaveDirector>,
+}
+
+/// Only the version, read first to reject incompatible files with a clear message
+#[derive(Deserialize)]
+struct SaveGameHeader {
+    version: u32,
+}
+
+impl SaveGame {
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).expect("save game is always serializable")
+    }
+
+    pub fn from_json(json: &str) -> Result<Self, String> {
+        let header: SaveGameHeader =
+            serde_json::from_str(json).map_err(|err| format!("save game: {}", err))?;
+        if header.version != SAVE_GAME_VERSION {
+            return Err(format!(
+                "save game: unsupported version {}, expected {}",
+                header.version, SAVE_GAME_VERSION
+            ));
+        }
+
+        serde_json::from_str(json).map_err(|err| format!("save game: {}", err))
+    }
+
+    /// Files are only available on desktop, in the browser this returns an error
+    pub fn write_to_file(&self, path: &str) -> Result<(), String> {
+        std::fs::write(path, self.to_json()).map_err(|err| format!("{}: {}", path, err))
+    }
+
+    pub fn read_from_file(path: &str) -> Result<Self, String> {
+        let json = std::fs::read_to_string(path).map_err(|err| format!("{}: {}", path, err))?;
+
+        Self::from_json(&json)
+    }
+}
+
+#[test]
+fn test_loaded_game_continues_identically() {
+    use std::sync::mpsc;
+
+    use crate::{
+        ant_controller::{AntAction, AntAnimation},
+        ant_generator::AntGenerator,
+        scenario::Scenario,
+        status_effect::StatusEffectSet,
+        unit_definition::UnitDefinitions,
+        worker::{Worker, WorkerMessage},
+    };
+
+    // what the render side shows of an ant: alive, health, animation and status effects
+    type RenderState = (bool, f32, AntAnimation, StatusEffectSet);
+    let update_render_states = |messages: &mpsc::Receiver<WorkerMessage>,
+                                states: &mut Vec<RenderState>| {
+        let snapshots = messages.try_iter().filter_map(|message| match message {
+            WorkerMessage::Snapshot(snapshot) => Some(snapshot),
+            _ => None,
+        });
+        for elem in snapshots.flat_map(|snapshot| snapshot.ant_actions) {
+            let state = &mut states[elem.index];
+            match elem.action {
+                AntAction::Spawn(_) => {
+                    *state = (true, 1.0, AntAnimation::Idle, StatusEffectSet::default())
+                }
+                AntAction::SetAnimation(animation) => state.2 = animation,
+                AntAction::SetHealth(health) => state.1 = health,
+                AntAction::SetStatusEffects(status_effects) => state.3 = status_effects,
+                // the death animation replaces the last one
+                AntAction::Die => *state = (false, state.1, AntAnimation::Idle, state.3),
+                _ => (),
+            }
+        }
+    };
+    let hidden: RenderState = (false, 0.0, AntAnimation::Idle, StatusEffectSet::default());
+
+    let ant_generator = AntGenerator::new(16);
+    let scenario = Scenario::from_ant_generator(&ant_generator);
+    let unit_definitions = UnitDefinitions::load_default().unwrap();
+
+    let create_worker = || {
+        let (_channel_0_tx, channel_0_rx) = mpsc::channel();
+        let (channel_1_tx, channel_1_rx) = mpsc::channel();
+        let worker = Worker::new(
+            channel_0_rx,
+            channel_1_tx,
+            scenario.clone(),
+            unit_definitions.clone(),
+            16,
+            16,
+        );
+        (worker, channel_1_rx)
+    };
+
+    // save in the middle of the battle
+    let (mut worker_0, messages_0) = create_worker();
+    let mut render_states_0 = vec![hidden; 16];
+    worker_0.start_battle(0).unwrap();
+    for tick in 0..700 {
+        worker_0.update(tick);
+    }
+    let save_game = SaveGame::from_json(&worker_0.save(700).to_json()).unwrap();
+    update_render_states(&messages_0, &mut render_states_0);
+    assert!(render_states_0.iter().any(|state| !state.0));
+    assert!(render_states_0.iter().any(|state| state.0 && state.1 < 1.0));
+
+    // the loaded worker runs on a clock starting at zero
+    let (mut worker_1, messages_1) = create_worker();
+    let mut render_states_1 = vec![hidden; 16];
+    worker_1.load(save_game, 0).unwrap();
+    for tick in 0..600 {
+        worker_0.update(700 + tick);
+        worker_1.update(tick);
+
+        // the loaded ants are shown as they were saved, not as freshly spawned ones
+        update_render_states(&messages_0, &mut render_states_0);
+        update_render_states(&messages_1, &mut render_states_1);
+        assert_eq!(render_states_0, render_states_1, "tick {}", tick);
+    }
+
+    let result_0 = worker_0.get_battle_result(1300);
+    let result_1 = worker_1.get_battle_result(1300);
+    for (unit_0, unit_1) in result_0.units.iter().zip(&result_1.units) {
+        assert_eq!(unit_0.health, unit_1.health);
+        assert_eq!(unit_0.damage_dealt, unit_1.damage_dealt);
+    }
+
+    let json = worker_0.save(1300).to_json().replacen(
+        &format!("\"version\":{}", SAVE_GAME_VERSION),
+        "\"version\":0",
+        1,
+    );
+    assert!(SaveGame::from_json(&json).is_err());
+}
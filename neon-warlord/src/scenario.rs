@@ -0,0 +1,154 @@
+//! Describes the armies a battle starts with
+
+use serde::{Deserialize, Serialize};
+
+use crate::{ant_generator::AntGenerator, game_board::Faction, unit_definition::UnitDefinitions};
+
+type Vec2 = cgmath::Vector2<f32>;
+
+/// Distance between two units of the same army
+pub const UNIT_SPACING: f32 = 4.0;
+
+/// Distance between the front lines of the two armies
+pub const ARMY_DISTANCE: f32 = 40.0;
+
+/// Random offset added to the spawn positions
+const POSITION_JITTER: f32 = 1.0;
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct UnitSpawn {
+    pub id: usize,
+    pub unit_type: String,
+    pub faction: Faction,
+    pub position: Vec2,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Scenario {
+    pub units: Vec<UnitSpawn>,
+}
+
+#[derive(Clone)]
+pub struct ArmyUnit {
+    pub unit_type: String,
+    pub count: usize,
+}
+
+/// All the units one faction brings into a battle
+#[derive(Clone)]
+pub struct Army {
+    pub faction: Faction,
+    pub units: Vec<ArmyUnit>,
+}
+
+impl Army {
+    /// Parses an army of the form "unit_type:count,unit_type:count"
+    pub fn parse(faction: Faction, definition: &str) -> Result<Self, String> {
+        let mut units = Vec::new();
+
+        for entry in definition.split(',') {
+            let (unit_type, count) = entry
+                .split_once(':')
+                .ok_or_else(|| format!("expected unit_type:count, found '{}'", entry))?;
+
+            let count = count
+                .trim()
+                .parse()
+                .map_err(|err| format!("invalid count in '{}': {}", entry, err))?;
+
+            units.push(ArmyUnit {
+                unit_type: unit_type.trim().to_string(),
+                count,
+            });
+        }
+
+        Ok(Self { faction, units })
+    }
+
+    pub fn validate(&self, unit_definitions: &UnitDefinitions) -> Result<(), String> {
+        for unit in &self.units {
+            unit_definitions.index_of(&unit.unit_type)?;
+        }
+
+        Ok(())
+    }
+
+    pub fn nr_units(&self) -> usize {
+        self.units.iter().map(|unit| unit.count).sum()
+    }
+
+    /// The unit types in the order of their first entry, without repetitions
+    pub fn unit_types(&self) -> Vec<&str> {
+        let mut unit_types = Vec::new();
+        for unit in &self.units {
+            if !unit_types.contains(&unit.unit_type.as_str()) {
+                unit_types.push(unit.unit_type.as_str());
+            }
+        }
+
+        unit_types
+    }
+}
+
+impl Scenario {
+    pub fn from_ant_generator(ant_generator: &AntGenerator) -> Self {
+        let units = ant_generator
+            .ants
+            .iter()
+            .map(|ant| UnitSpawn {
+                id: ant.id,
+                unit_type: "ant".to_string(),
+                faction: ant.faction,
+                position: ant.pos,
+            })
+            .collect();
+
+        Self { units }
+    }
+
+    /// Places the armies in square blocks facing each other, the seed jitters the positions
+    pub fn from_armies(armies: &[Army], seed: u64) -> Self {
+        let mut rng = fastrand::Rng::with_seed(seed);
+        let mut units = Vec::new();
+
+        for (army_index, army) in armies.iter().enumerate() {
+            let columns = army.nr_units().isqrt().max(1);
+
+            // the second army is mirrored behind its front line
+            let (front_line, direction) = if army_index % 2 == 0 {
+                (0.0, -1.0)
+            } else {
+                (ARMY_DISTANCE, 1.0)
+            };
+
+            let unit_types = army
+                .units
+                .iter()
+                .flat_map(|unit| std::iter::repeat_n(&unit.unit_type, unit.count));
+
+            for (i, unit_type) in unit_types.enumerate() {
+                let column = i % columns;
+                let row = i / columns;
+
+                let jitter = Vec2::new(
+                    (rng.f32() * 2.0 - 1.0) * POSITION_JITTER,
+                    (rng.f32() * 2.0 - 1.0) * POSITION_JITTER,
+                );
+
+                let position = Vec2::new(
+                    front_line + direction * row as f32 * UNIT_SPACING,
+                    column as f32 * UNIT_SPACING,
+                ) + jitter;
+
+                units.push(UnitSpawn {
+                    id: units.len(),
+                    unit_type: unit_type.clone(),
+                    faction: army.faction,
+                    position,
+                });
+            }
+        }
+
+        Self { units }
+    }
+}
@@ -2,7 +2,7 @@
 
 use forward_renderer::{RendererSettings, TerrainSettings, animation_shader, lod_heightmap_shader};
 
-use crate::{CameraSettings, ObjectSettings};
+use crate::{CameraSettings, ObjectSettings, heightmap_generator};
 
 pub struct Settings {}
 impl Settings {
@@ -24,13 +24,18 @@ impl Settings {
 
     pub fn get_terrain_settings(&self) -> TerrainSettings {
         TerrainSettings {
-            nr_tiles: 32,
+            nr_tiles: heightmap_generator::NR_TILES,
             max_depth: 5,
         }
     }
 
     pub fn get_object_settings(&self) -> ObjectSettings {
-        ObjectSettings { max_nr_ants: 9 }
+        ObjectSettings {
+            nr_start_ants: 9,
+            max_nr_ants: 16,
+            max_nr_orbs: 16,
+            enemy_waves: false,
+        }
     }
 
     pub(crate) fn get_camera_settings(&self) -> CameraSettings {
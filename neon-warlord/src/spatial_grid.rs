@@ -0,0 +1,243 @@
+//! Uniform grid over the agents of the game board, rebuilt every tick
+//!
+//! The agents are sorted into their cells by a counting sort, the queries only visit the cells
+//! overlapping the searched area.
+
+use cgmath::InnerSpace;
+
+type Vec2 = cgmath::Vector2<f32>;
+
+/// Edge length of a cell, about the range of an ant
+const CELL_SIZE: f32 = 8.0;
+
+/// The cells grow if the agents are spread over a large area
+const MAX_CELLS_PER_AGENT: usize = 4;
+
+#[derive(Clone)]
+pub struct SpatialGrid {
+    origin: Vec2,
+    cell_size: f32,
+    size_x: usize,
+    size_y: usize,
+
+    cell_starts: Vec<usize>, // start of each cell in the entries, one more than cells
+    entries: Vec<usize>,     // indices of the agents, sorted by cell
+}
+
+impl SpatialGrid {
+    pub fn new() -> Self {
+        Self {
+            origin: Vec2::new(0.0, 0.0),
+            cell_size: CELL_SIZE,
+            size_x: 0,
+            size_y: 0,
+            cell_starts: Vec::new(),
+            entries: Vec::new(),
+        }
+    }
+
+    pub fn rebuild(&mut self, positions: &[Vec2]) {
+        self.entries.clear();
+        self.cell_starts.clear();
+
+        if positions.is_empty() {
+            self.size_x = 0;
+            self.size_y = 0;
+            return;
+        }
+
+        // bounds
+        let mut min = positions[0];
+        let mut max = positions[0];
+        for position in positions {
+            min.x = min.x.min(position.x);
+            min.y = min.y.min(position.y);
+            max.x = max.x.max(position.x);
+            max.y = max.y.max(position.y);
+        }
+        let extent = max - min;
+
+        let max_cells = (positions.len() * MAX_CELLS_PER_AGENT).max(64) as f32;
+        self.cell_size = CELL_SIZE.max((extent.x * extent.y / max_cells).sqrt());
+        self.origin = min;
+        self.size_x = (extent.x / self.cell_size) as usize + 1;
+        self.size_y = (extent.y / self.cell_size) as usize + 1;
+
+        // counting sort of the agents into the cells
+        let nr_cells = self.size_x * self.size_y;
+        self.cell_starts.resize(nr_cells + 1, 0);
+        for position in positions {
+            let cell = self.cell_index(*position);
+            self.cell_starts[cell + 1] += 1;
+        }
+        for cell in 0..nr_cells {
+            self.cell_starts[cell + 1] += self.cell_starts[cell];
+        }
+
+        let mut next = self.cell_starts.clone();
+        self.entries.resize(positions.len(), 0);
+        for (i, position) in positions.iter().enumerate() {
+            let cell = self.cell_index(*position);
+            self.entries[next[cell]] = i;
+            next[cell] += 1;
+        }
+    }
+
+    fn cell_coordinates(&self, position: Vec2) -> (isize, isize) {
+        let local = (position - self.origin) / self.cell_size;
+
+        (local.x.floor() as isize, local.y.floor() as isize)
+    }
+
+    fn cell_index(&self, position: Vec2) -> usize {
+        let (x, y) = self.cell_coordinates(position);
+        let x = x.clamp(0, self.size_x as isize - 1) as usize;
+        let y = y.clamp(0, self.size_y as isize - 1) as usize;
+
+        y * self.size_x + x
+    }
+
+    /// Calls the function with all agents in the cells from min to max, both included
+    fn for_each_in_cells(
+        &self,
+        min: (isize, isize),
+        max: (isize, isize),
+        f: &mut dyn FnMut(usize),
+    ) {
+        let min_x = min.0.max(0);
+        let min_y = min.1.max(0);
+        let max_x = max.0.min(self.size_x as isize - 1);
+        let max_y = max.1.min(self.size_y as isize - 1);
+
+        for y in min_y..=max_y {
+            for x in min_x..=max_x {
+                let cell = y as usize * self.size_x + x as usize;
+                for &index in &self.entries[self.cell_starts[cell]..self.cell_starts[cell + 1]] {
+                    f(index);
+                }
+            }
+        }
+    }
+
+    /// The closest agent accepted by the filter, a tie goes to the lower index
+    pub fn nearest(
+        &self,
+        positions: &[Vec2],
+        position: Vec2,
+        filter: &dyn Fn(usize) -> bool,
+    ) -> Option<usize> {
+        if self.entries.is_empty() {
+            return None;
+        }
+
+        let center = self.cell_coordinates(position);
+        let mut best: Option<(f32, usize)> = None;
+
+        // search in rings of cells around the position
+        let max_ring = self.size_x.max(self.size_y) as isize
+            + center.0.unsigned_abs().max(center.1.unsigned_abs()) as isize;
+        for ring in 0..=max_ring {
+            let mut visit = |index: usize| {
+                if !filter(index) {
+                    return;
+                }
+                let distance = (positions[index] - position).magnitude2();
+                let is_better = match best {
+                    Some((best_distance, best_index)) => {
+                        distance < best_distance
+                            || (distance == best_distance && index < best_index)
+                    }
+                    None => true,
+                };
+                if is_better {
+                    best = Some((distance, index));
+                }
+            };
+
+            if ring == 0 {
+                self.for_each_in_cells(center, center, &mut visit);
+            } else {
+                let (x0, y0) = (center.0 - ring, center.1 - ring);
+                let (x1, y1) = (center.0 + ring, center.1 + ring);
+                self.for_each_in_cells((x0, y0), (x1, y0), &mut visit);
+                self.for_each_in_cells((x0, y1), (x1, y1), &mut visit);
+                self.for_each_in_cells((x0, y0 + 1), (x0, y1 - 1), &mut visit);
+                self.for_each_in_cells((x1, y0 + 1), (x1, y1 - 1), &mut visit);
+            }
+
+            // agents in the next rings are at least this far away
+            if let Some((best_distance, _)) = best {
+                let min_distance = ring as f32 * self.cell_size;
+                if best_distance <= min_distance * min_distance {
+                    break;
+                }
+            }
+        }
+
+        best.map(|(_, index)| index)
+    }
+
+    /// Indices of all agents within the radius, in ascending order
+    pub fn within_radius(
+        &self,
+        positions: &[Vec2],
+        position: Vec2,
+        radius: f32,
+        indices: &mut Vec<usize>,
+    ) {
+        if self.entries.is_empty() {
+            return;
+        }
+
+        let start = indices.len();
+        let min = self.cell_coordinates(position - Vec2::new(radius, radius));
+        let max = self.cell_coordinates(position + Vec2::new(radius, radius));
+        self.for_each_in_cells(min, max, &mut |index| {
+            if (positions[index] - position).magnitude2() <= radius * radius {
+                indices.push(index);
+            }
+        });
+
+        // independent of the order of the cells
+        indices[start..].sort_unstable();
+    }
+}
+
+/// Needed for the skipped field of the game board, the grid is rebuilt after loading
+impl Default for SpatialGrid {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[test]
+fn test_queries_match_a_linear_scan() {
+    let mut rng = fastrand::Rng::with_seed(7);
+    let positions: Vec<Vec2> = (0..500)
+        .map(|_| Vec2::new(rng.f32() * 200.0 - 100.0, rng.f32() * 60.0))
+        .collect();
+
+    let mut grid = SpatialGrid::new();
+    grid.rebuild(&positions);
+
+    for _ in 0..50 {
+        let position = Vec2::new(rng.f32() * 300.0 - 150.0, rng.f32() * 100.0 - 20.0);
+        let filter = |index: usize| !index.is_multiple_of(3);
+
+        let expected = (0..positions.len())
+            .filter(|&index| filter(index))
+            .min_by(|&a, &b| {
+                let distance_a = (positions[a] - position).magnitude2();
+                let distance_b = (positions[b] - position).magnitude2();
+                distance_a.total_cmp(&distance_b)
+            });
+        assert_eq!(grid.nearest(&positions, position, &filter), expected);
+
+        let expected: Vec<usize> = (0..positions.len())
+            .filter(|&index| (positions[index] - position).magnitude2() <= 15.0 * 15.0)
+            .collect();
+        let mut indices = Vec::new();
+        grid.within_radius(&positions, position, 15.0, &mut indices);
+        assert_eq!(indices, expected);
+    }
+}
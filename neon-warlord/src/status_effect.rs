@@ -0,0 +1,283 @@
+//! Temporary effects on a unit, applied by hits and expiring after their duration
+//!
+//! Every kind has its own stacking rule: slow, stun and haste refresh a single effect, shields
+//! add up and burns and armor shreds stack as independent effects up to a limit.
+
+use serde::{Deserialize, Serialize};
+
+use crate::unit_definition;
+
+/// Number of burns and armor shreds a unit can suffer at the same time
+const MAX_STACKS: usize = 3;
+
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StatusEffectKind {
+    Slow,       // strength is the fraction of the speed lost
+    Stun,       // the unit can neither move nor shoot, the strength is ignored
+    Burn,       // strength is the damage per second
+    Shield,     // strength is the damage absorbed
+    Haste,      // strength is the fraction of speed gained
+    ArmorShred, // strength is the fraction of additional damage taken
+}
+
+impl StatusEffectKind {
+    fn stacking(self) -> Stacking {
+        match self {
+            StatusEffectKind::Slow | StatusEffectKind::Stun | StatusEffectKind::Haste => {
+                Stacking::Refresh
+            }
+            StatusEffectKind::Shield => Stacking::Add,
+            StatusEffectKind::Burn | StatusEffectKind::ArmorShred => Stacking::Stack,
+        }
+    }
+}
+
+/// How a new effect combines with the active ones of the same kind
+enum Stacking {
+    Refresh, // one effect, keeping the higher strength and the longer duration
+    Add,     // one effect, the strengths add up and the duration is refreshed
+    Stack,   // independent effects up to MAX_STACKS, the oldest is replaced
+}
+
+/// An effect as given in the unit definitions, in seconds
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct StatusEffectDefinition {
+    pub kind: StatusEffectKind,
+    pub duration: f32,
+    pub strength: f32,
+}
+
+impl StatusEffectDefinition {
+    pub fn validate(&self) -> Result<(), String> {
+        if !self.duration.is_finite() || self.duration <= 0.0 {
+            return Err(format!(
+                "effect.duration must be positive, found {}",
+                self.duration
+            ));
+        }
+        if !self.strength.is_finite() || self.strength < 0.0 {
+            return Err(format!(
+                "effect.strength must not be negative, found {}",
+                self.strength
+            ));
+        }
+        if self.kind == StatusEffectKind::Slow && self.strength > 1.0 {
+            return Err(format!(
+                "effect.strength of a slow must be at most 1.0, found {}",
+                self.strength
+            ));
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+pub struct StatusEffect {
+    pub kind: StatusEffectKind,
+    pub source_id: usize, // unit which applied the effect
+    pub remaining: u64,   // ticks until the effect expires
+    pub strength: f32,    // per tick for burns
+}
+
+impl StatusEffect {
+    pub fn new(definition: &StatusEffectDefinition, source_id: usize) -> Self {
+        let strength = match definition.kind {
+            StatusEffectKind::Burn => unit_definition::per_second_to_per_tick(definition.strength),
+            _ => definition.strength,
+        };
+
+        Self {
+            kind: definition.kind,
+            source_id,
+            remaining: unit_definition::seconds_to_ticks(definition.duration),
+            strength,
+        }
+    }
+}
+
+/// The kinds of the active effects, sent to the renderer
+#[derive(Clone, Copy, PartialEq, Default, Debug)]
+pub struct StatusEffectSet(u8);
+
+impl StatusEffectSet {
+    pub fn contains(&self, kind: StatusEffectKind) -> bool {
+        self.0 & Self::bit(kind) != 0
+    }
+
+    fn insert(&mut self, kind: StatusEffectKind) {
+        self.0 |= Self::bit(kind);
+    }
+
+    fn bit(kind: StatusEffectKind) -> u8 {
+        1 << kind as u8
+    }
+}
+
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub struct StatusEffects {
+    effects: Vec<StatusEffect>,
+}
+
+impl StatusEffects {
+    pub fn new() -> Self {
+        Self {
+            effects: Vec::new(),
+        }
+    }
+
+    pub fn apply(&mut self, effect: StatusEffect) {
+        let mut same_kind = self
+            .effects
+            .iter_mut()
+            .filter(|elem| elem.kind == effect.kind);
+
+        match effect.kind.stacking() {
+            Stacking::Refresh => match same_kind.next() {
+                Some(active) => {
+                    if effect.strength > active.strength {
+                        active.strength = effect.strength;
+                        active.source_id = effect.source_id;
+                    }
+                    active.remaining = active.remaining.max(effect.remaining);
+                }
+                None => self.effects.push(effect),
+            },
+            Stacking::Add => match same_kind.next() {
+                Some(active) => {
+                    active.strength += effect.strength;
+                    active.remaining = active.remaining.max(effect.remaining);
+                }
+                None => self.effects.push(effect),
+            },
+            Stacking::Stack => {
+                if same_kind.count() < MAX_STACKS {
+                    self.effects.push(effect);
+                } else if let Some(oldest) = self
+                    .effects
+                    .iter_mut()
+                    .filter(|elem| elem.kind == effect.kind)
+                    .min_by_key(|elem| elem.remaining)
+                {
+                    *oldest = effect;
+                }
+            }
+        }
+    }
+
+    /// Counts down the durations and drops the expired effects
+    pub fn update(&mut self) {
+        for effect in &mut self.effects {
+            effect.remaining = effect.remaining.saturating_sub(1);
+        }
+        self.effects.retain(|effect| effect.remaining > 0);
+    }
+
+    pub fn clear(&mut self) {
+        self.effects.clear();
+    }
+
+    pub fn burns(&self) -> impl Iterator<Item = &StatusEffect> {
+        self.effects
+            .iter()
+            .filter(|effect| effect.kind == StatusEffectKind::Burn)
+    }
+
+    pub fn is_stunned(&self) -> bool {
+        self.strength(StatusEffectKind::Stun).is_some()
+    }
+
+    /// Factor the speed is scaled by
+    pub fn speed_factor(&self) -> f32 {
+        let slow = self.strength(StatusEffectKind::Slow).unwrap_or(0.0);
+        let haste = self.strength(StatusEffectKind::Haste).unwrap_or(0.0);
+
+        (1.0 - slow) * (1.0 + haste)
+    }
+
+    /// Returns the damage left after the shred and the shield, the shield is used up
+    pub fn absorb(&mut self, damage: f32) -> f32 {
+        let shred: f32 = self
+            .effects
+            .iter()
+            .filter(|effect| effect.kind == StatusEffectKind::ArmorShred)
+            .map(|effect| effect.strength)
+            .sum();
+        let mut damage = damage * (1.0 + shred);
+
+        if let Some(shield) = self
+            .effects
+            .iter_mut()
+            .find(|effect| effect.kind == StatusEffectKind::Shield)
+        {
+            let absorbed = damage.min(shield.strength);
+            shield.strength -= absorbed;
+            damage -= absorbed;
+        }
+        self.effects
+            .retain(|effect| effect.kind != StatusEffectKind::Shield || effect.strength > 0.0);
+
+        damage
+    }
+
+    pub fn kinds(&self) -> StatusEffectSet {
+        let mut kinds = StatusEffectSet::default();
+        for effect in &self.effects {
+            kinds.insert(effect.kind);
+        }
+
+        kinds
+    }
+
+    fn strength(&self, kind: StatusEffectKind) -> Option<f32> {
+        self.effects
+            .iter()
+            .find(|effect| effect.kind == kind)
+            .map(|effect| effect.strength)
+    }
+}
+
+#[test]
+fn test_effects_stack_refresh_and_expire() {
+    let effect = |kind, duration, strength| {
+        StatusEffect::new(
+            &StatusEffectDefinition {
+                kind,
+                duration,
+                strength,
+            },
+            0,
+        )
+    };
+    let mut effects = StatusEffects::new();
+
+    // a stronger slow replaces the weaker one, the longer duration is kept
+    effects.apply(effect(StatusEffectKind::Slow, 2.0, 0.25));
+    effects.apply(effect(StatusEffectKind::Slow, 1.0, 0.5));
+    assert_eq!(effects.speed_factor(), 0.5);
+    assert_eq!(effects.effects.len(), 1);
+    assert_eq!(effects.effects[0].remaining, 120);
+
+    // burns stack up to the limit
+    for _ in 0..MAX_STACKS + 2 {
+        effects.apply(effect(StatusEffectKind::Burn, 1.0, 60.0));
+    }
+    assert_eq!(effects.burns().count(), MAX_STACKS);
+
+    // shields add up and absorb the damage increased by the shred
+    effects.apply(effect(StatusEffectKind::Shield, 1.0, 10.0));
+    effects.apply(effect(StatusEffectKind::Shield, 1.0, 10.0));
+    effects.apply(effect(StatusEffectKind::ArmorShred, 1.0, 0.5));
+    assert_eq!(effects.absorb(10.0), 0.0);
+    assert_eq!(effects.absorb(10.0), 10.0);
+    assert!(!effects.kinds().contains(StatusEffectKind::Shield));
+
+    for _ in 0..60 {
+        effects.update();
+    }
+    assert!(effects.kinds().contains(StatusEffectKind::Slow));
+    assert!(!effects.kinds().contains(StatusEffectKind::Burn));
+    assert!(!effects.kinds().contains(StatusEffectKind::ArmorShred));
+}
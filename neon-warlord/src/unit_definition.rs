@@ -0,0 +1,249 @@
+//! Parameters of all unit types, loaded from res/units.json
+//!
+//! Speeds are given in units per second and durations in seconds, they are converted to ticks
+//! by the controllers.
+
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    ability::AbilityDefinition, ant_ai::behaviour_tree::NodeDefinition, game_board::TargetStrategy,
+    resources, status_effect::StatusEffectDefinition, worker::TICKS_PER_SECOND,
+};
+
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct UnitStats {
+    pub max_health: f32,
+    pub speed: f32,
+    pub range: f32,
+    pub charge_duration: f32, // the ant charges the shot, then the orb grows as long before launching
+    pub shot_damage: f32,
+}
+
+/// Names of the glTF animations played for each state of the ant
+///
+/// A model without a charge clip keeps playing its current one while charging, a model without a
+/// death clip fades out at once.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct AnimationNames {
+    pub idle: String,
+    pub walk: String,
+    #[serde(default)]
+    pub charge_shot: Option<String>,
+    #[serde(default)]
+    pub death: Option<String>, // played once before the ant fades out
+}
+
+/// A glb file shared by unit types, swapping the file only requires to update its table
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ModelDefinition {
+    pub animations: AnimationNames,
+}
+
+/// How the unit picks its targets, a retarget cooldown above zero makes the targets sticky
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TargetingDefinition {
+    pub strategy: TargetStrategy,
+    pub retarget_cooldown: f32,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ProjectileType {
+    PlasmaOrb,
+}
+
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ProjectileDefinition {
+    #[serde(rename = "type")]
+    pub projectile_type: ProjectileType,
+    pub speed: f32,
+    pub max_scale: f32,
+    pub explosion_radius: f32,
+    #[serde(default)]
+    pub effect: Option<StatusEffectDefinition>, // applied to every unit hit by the explosion
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct UnitDefinition {
+    pub name: String,
+    pub cost: u32,
+    pub model: String, // name of the glb file in the res folder, a key of the models
+    pub brain: String, // name of the behaviour tree controlling the unit
+    pub targeting: TargetingDefinition,
+    pub stats: UnitStats,
+    pub projectile: ProjectileDefinition,
+    #[serde(default)]
+    pub ability: Option<AbilityDefinition>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct UnitDefinitions {
+    pub brains: BTreeMap<String, NodeDefinition>,
+    pub models: BTreeMap<String, ModelDefinition>,
+    pub units: Vec<UnitDefinition>,
+}
+
+impl UnitDefinitions {
+    /// The definitions in the res folder
+    pub fn load_default() -> Result<Self, String> {
+        Self::parse(&resources::read_to_string("units.json")?)
+    }
+
+    pub fn parse(json: &str) -> Result<Self, String> {
+        let definitions: Self =
+            serde_json::from_str(json).map_err(|err| format!("units.json: {}", err))?;
+
+        definitions.validate()?;
+
+        Ok(definitions)
+    }
+
+    pub fn get(&self, name: &str) -> Result<&UnitDefinition, String> {
+        self.index_of(name).map(|index| &self.units[index])
+    }
+
+    pub fn brain(&self, name: &str) -> Result<&NodeDefinition, String> {
+        self.brains.get(name).ok_or_else(|| {
+            let names: Vec<&str> = self.brains.keys().map(|name| name.as_str()).collect();
+            format!(
+                "unknown brain '{}', expected one of: {}",
+                name,
+                names.join(", ")
+            )
+        })
+    }
+
+    pub fn model(&self, name: &str) -> Result<&ModelDefinition, String> {
+        self.models.get(name).ok_or_else(|| {
+            let names: Vec<&str> = self.models.keys().map(|name| name.as_str()).collect();
+            format!(
+                "unknown model '{}', expected one of: {}",
+                name,
+                names.join(", ")
+            )
+        })
+    }
+
+    pub fn index_of(&self, name: &str) -> Result<usize, String> {
+        self.units
+            .iter()
+            .position(|unit| unit.name == name)
+            .ok_or_else(|| {
+                let names: Vec<&str> = self.units.iter().map(|unit| unit.name.as_str()).collect();
+                format!(
+                    "unknown unit type '{}', expected one of: {}",
+                    name,
+                    names.join(", ")
+                )
+            })
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if self.units.is_empty() {
+            return Err("units.json: no units defined".to_string());
+        }
+
+        for (name, brain) in &self.brains {
+            brain
+                .validate()
+                .map_err(|err| format!("units.json: brain '{}': {}", name, err))?;
+        }
+
+        for (i, unit) in self.units.iter().enumerate() {
+            let context =
+                |message: String| format!("units.json: unit '{}': {}", unit.name, message);
+
+            if unit.name.is_empty() {
+                return Err(format!("units.json: unit {} has an empty name", i));
+            }
+            if self.units[..i].iter().any(|other| other.name == unit.name) {
+                return Err(context("defined more than once".to_string()));
+            }
+            self.model(&unit.model).map_err(context)?;
+            self.brain(&unit.brain).map_err(context)?;
+
+            let retarget_cooldown = unit.targeting.retarget_cooldown;
+            if !retarget_cooldown.is_finite() || retarget_cooldown < 0.0 {
+                return Err(context(format!(
+                    "targeting.retarget_cooldown must not be negative, found {}",
+                    retarget_cooldown
+                )));
+            }
+
+            let stats = &unit.stats;
+            let projectile = &unit.projectile;
+            let values = [
+                ("stats.max_health", stats.max_health),
+                ("stats.speed", stats.speed),
+                ("stats.range", stats.range),
+                ("stats.charge_duration", stats.charge_duration),
+                ("stats.shot_damage", stats.shot_damage),
+                ("projectile.speed", projectile.speed),
+                ("projectile.max_scale", projectile.max_scale),
+                ("projectile.explosion_radius", projectile.explosion_radius),
+            ];
+            for (field, value) in values {
+                if !value.is_finite() || value <= 0.0 {
+                    return Err(context(format!(
+                        "{} must be positive, found {}",
+                        field, value
+                    )));
+                }
+            }
+            if let Some(effect) = &projectile.effect {
+                effect
+                    .validate()
+                    .map_err(|err| context(format!("projectile.{}", err)))?;
+            }
+            if let Some(ability) = &unit.ability {
+                ability.validate(self).map_err(context)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Converts a duration in seconds to simulation ticks
+pub fn seconds_to_ticks(seconds: f32) -> u64 {
+    (seconds * TICKS_PER_SECOND as f32).round() as u64
+}
+
+/// Converts a speed in units per second to units per tick
+pub fn per_second_to_per_tick(speed: f32) -> f32 {
+    speed / TICKS_PER_SECOND as f32
+}
+
+#[test]
+fn test_invalid_definitions_are_rejected() {
+    assert!(UnitDefinitions::load_default().is_ok());
+
+    let json = include_str!("../res/units.json").replace("\"speed\": 2.4", "\"speed\": -2.4");
+    let err = UnitDefinitions::parse(&json).unwrap_err();
+    assert!(
+        err.contains("'ant'") && err.contains("stats.speed"),
+        "{}",
+        err
+    );
+
+    let json = include_str!("../res/units.json").replace("\"range\"", "\"rang\"");
+    assert!(UnitDefinitions::parse(&json).is_err());
+
+    // every unit needs the animation table of its model
+    let json = include_str!("../res/units.json").replacen(
+        "\"model\": \"ant_0_10.glb\"",
+        "\"model\": \"ant_0_8.glb\"",
+        1,
+    );
+    let err = UnitDefinitions::parse(&json).unwrap_err();
+    assert!(err.contains("unknown model 'ant_0_8.glb'"), "{}", err);
+}
@@ -0,0 +1,303 @@
+//! Enemy waves of the PvE mode, defined in res/waves.json
+//!
+//! Every round the director spawns the next wave of red units at walkable points behind the red
+//! front line. The rounds after the last wave repeat it, and every round makes the units tougher.
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    heightmap_generator::HeightMapGenerator,
+    navigation::NavigationGrid,
+    resources,
+    scenario::{ARMY_DISTANCE, UNIT_SPACING},
+    unit_definition::{self, UnitDefinitions},
+};
+
+type Vec2 = cgmath::Vector2<f32>;
+
+/// Distance of the spawn points behind the red front line
+const SPAWN_DEPTH: f32 = 12.0;
+
+/// The groups of a wave take turns between these spawn points along the front
+const NR_SPAWN_POINTS: usize = 4;
+const SPAWN_POINT_SPACING: f32 = 8.0;
+
+/// Units of a group are placed in rows of this many units behind their spawn point
+const GROUP_COLUMNS: usize = 3;
+
+/// Units arriving together, the delay is counted in seconds from the start of the battle
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct WaveGroup {
+    pub unit_type: String,
+    pub count: usize,
+    pub delay: f32,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Wave {
+    pub groups: Vec<WaveGroup>,
+}
+
+/// Fractions of the base values added per round after the first
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct DifficultyScaling {
+    pub health_per_round: f32,
+    pub damage_per_round: f32,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct WaveScript {
+    pub scaling: DifficultyScaling,
+    pub waves: Vec<Wave>,
+}
+
+impl WaveScript {
+    /// The script in the res folder
+    pub fn load_default(unit_definitions: &UnitDefinitions) -> Result<Self, String> {
+        Self::parse(&resources::read_to_string("waves.json")?, unit_definitions)
+    }
+
+    pub fn parse(json: &str, unit_definitions: &UnitDefinitions) -> Result<Self, String> {
+        let script: Self =
+            serde_json::from_str(json).map_err(|err| format!("waves.json: {}", err))?;
+
+        script.validate(unit_definitions)?;
+
+        Ok(script)
+    }
+
+    pub fn validate(&self, unit_definitions: &UnitDefinitions) -> Result<(), String> {
+        if self.waves.is_empty() {
+            return Err("waves.json: no waves defined".to_string());
+        }
+
+        let scaling = [
+            ("scaling.health_per_round", self.scaling.health_per_round),
+            ("scaling.damage_per_round", self.scaling.damage_per_round),
+        ];
+        for (field, value) in scaling {
+            if !value.is_finite() || value < 0.0 {
+                return Err(format!(
+                    "waves.json: {} must not be negative, found {}",
+                    field, value
+                ));
+            }
+        }
+
+        for (i, wave) in self.waves.iter().enumerate() {
+            let context = |message: String| format!("waves.json: wave {}: {}", i + 1, message);
+
+            if wave.groups.iter().all(|group| group.count == 0) {
+                return Err(context("no units".to_string()));
+            }
+            for group in &wave.groups {
+                unit_definitions
+                    .index_of(&group.unit_type)
+                    .map_err(context)?;
+                if !group.delay.is_finite() || group.delay < 0.0 {
+                    return Err(context(format!(
+                        "delay must not be negative, found {}",
+                        group.delay
+                    )));
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// The wave fought in the round, the last wave is repeated
+    pub fn wave(&self, round: u32) -> (usize, &Wave) {
+        let index = (round.max(1) as usize - 1).min(self.waves.len() - 1);
+        (index, &self.waves[index])
+    }
+}
+
+/// How far the current wave is, sent to the main thread
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct WaveProgress {
+    pub round: u32,
+    pub wave: usize, // starting at 1, repeated after the last wave
+    pub nr_spawned: usize,
+    pub nr_units: usize,
+    pub nr_alive: usize,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct WaveSpawn {
+    pub tick: u64,
+    pub unit_type: String,
+    pub position: Vec2,
+}
+
+/// Spawns the waves of the script, one per round
+#[derive(Clone, Serialize, Deserialize)]
+pub struct WaveDirector {
+    script: WaveScript,
+
+    round: u32,
+    spawns: Vec<WaveSpawn>, // all units of the current wave ordered by their tick
+    nr_spawned: usize,
+}
+
+impl WaveDirector {
+    pub fn new(script: WaveScript) -> Self {
+        Self {
+            script,
+            round: 0,
+            spawns: Vec::new(),
+            nr_spawned: 0,
+        }
+    }
+
+    pub fn script(&self) -> &WaveScript {
+        &self.script
+    }
+
+    /// Plans the spawns of the wave of the round, the positions are moved onto walkable terrain
+    pub fn start(
+        &mut self,
+        round: u32,
+        tick: u64,
+        terrain: &HeightMapGenerator,
+        navigation: &mut NavigationGrid,
+    ) {
+        let (_, wave) = self.script.wave(round);
+
+        let mut spawns = Vec::new();
+        for (group_index, group) in wave.groups.iter().enumerate() {
+            let spawn_point = Vec2::new(
+                ARMY_DISTANCE + SPAWN_DEPTH,
+                (group_index % NR_SPAWN_POINTS) as f32 * SPAWN_POINT_SPACING,
+            );
+
+            for i in 0..group.count {
+                let offset = Vec2::new(
+                    (i / GROUP_COLUMNS) as f32,
+                    (i % GROUP_COLUMNS) as f32 - (GROUP_COLUMNS / 2) as f32,
+                ) * UNIT_SPACING;
+                let position = spawn_point + offset;
+
+                spawns.push(WaveSpawn {
+                    tick: tick + unit_definition::seconds_to_ticks(group.delay),
+                    unit_type: group.unit_type.clone(),
+                    position: navigation
+                        .nearest_walkable_position(terrain, position)
+                        .unwrap_or(position),
+                });
+            }
+        }
+        // stable, the groups with the same delay keep their order
+        spawns.sort_by_key(|spawn| spawn.tick);
+
+        self.round = round;
+        self.spawns = spawns;
+        self.nr_spawned = 0;
+    }
+
+    /// The next unit due at the tick
+    pub fn next_spawn(&mut self, tick: u64) -> Option<WaveSpawn> {
+        let spawn = self.spawns.get(self.nr_spawned)?;
+        if spawn.tick > tick {
+            return None;
+        }
+
+        self.nr_spawned += 1;
+        Some(spawn.clone())
+    }
+
+    /// Units of the wave are still to come
+    pub fn is_spawning(&self) -> bool {
+        self.nr_spawned < self.spawns.len()
+    }
+
+    pub fn health_factor(&self) -> f32 {
+        1.0 + self.script.scaling.health_per_round * self.round.saturating_sub(1) as f32
+    }
+
+    pub fn damage_factor(&self) -> f32 {
+        1.0 + self.script.scaling.damage_per_round * self.round.saturating_sub(1) as f32
+    }
+
+    pub fn progress(&self, nr_alive: usize) -> WaveProgress {
+        let (index, _) = self.script.wave(self.round);
+
+        WaveProgress {
+            round: self.round,
+            wave: index + 1,
+            nr_spawned: self.nr_spawned,
+            nr_units: self.spawns.len(),
+            nr_alive,
+        }
+    }
+}
+
+#[test]
+fn test_waves_spawn_and_scale_with_the_round() {
+    use std::sync::mpsc;
+
+    use crate::{
+        game_board::Faction,
+        scenario::{Army, Scenario},
+        worker::{Worker, WorkerMessage},
+    };
+
+    let unit_definitions = UnitDefinitions::load_default().unwrap();
+    let script = WaveScript::load_default(&unit_definitions).unwrap();
+
+    // the rounds after the last wave repeat it with tougher units
+    let mut director = WaveDirector::new(script.clone());
+    let terrain = HeightMapGenerator::new();
+    let mut navigation = NavigationGrid::new();
+    director.start(100, 0, &terrain, &mut navigation);
+    assert_eq!(director.progress(0).wave, script.waves.len());
+    assert!(director.health_factor() > 1.0 && director.damage_factor() > 1.0);
+
+    // without enemies in the scenario the worker spawns the first wave
+    let army = Army::parse(Faction::Blue, "heavy_ant:4").unwrap();
+    let scenario = Scenario::from_armies(&[army], 0);
+    let (_channel_0_tx, channel_0_rx) = mpsc::channel();
+    let (channel_1_tx, channel_1_rx) = mpsc::channel();
+    let mut worker = Worker::new(
+        channel_0_rx,
+        channel_1_tx,
+        scenario,
+        unit_definitions,
+        16,
+        16,
+    );
+    worker.start_battle(0).unwrap();
+    assert!(!worker.is_battle_finished());
+
+    let mut tick = 0;
+    while !worker.is_battle_finished() && tick < 60 * 60 * 5 {
+        worker.update(tick);
+        tick += 1;
+    }
+
+    let progress: Vec<WaveProgress> = channel_1_rx
+        .try_iter()
+        .filter_map(|message| match message {
+            WorkerMessage::WaveProgress(progress) => Some(progress),
+            _ => None,
+        })
+        .collect();
+    let nr_units = script.waves[0].groups.iter().map(|group| group.count).sum();
+    let last = progress.last().unwrap();
+    assert_eq!(
+        (last.wave, last.nr_spawned, last.nr_units),
+        (1, nr_units, nr_units)
+    );
+
+    let result = worker.get_battle_result(tick);
+    let nr_red = result
+        .units
+        .iter()
+        .filter(|unit| unit.faction == Faction::Red)
+        .count();
+    assert_eq!(nr_red, nr_units);
+}
@@ -12,16 +12,40 @@ use wgpu_renderer::performance_monitor::{
 };
 
 use crate::{
-    ant_ai::AntAi,
-    ant_controller::{AntActionStruct, AntController},
+    ability::{AbilityKind, Cast, MANA_PER_DAMAGE_DEALT, SUMMON_DISTANCE},
+    ant_ai::WorldInterface,
+    ant_controller::{AntAction, AntActionStruct},
+    ant_unit::AntUnit,
+    battle_event::{BattleEvent, BattleEventKind},
+    battle_result::{BattleResult, UnitResult},
+    combat::{Combat, Hit},
     game_board::{Faction, GameBoard},
+    game_flow::{GameCommand, GameFlow, GamePhase, GameState, RoundResult},
     heightmap_generator::HeightMapGenerator,
+    navigation::NavigationGrid,
+    orb_controller::OrbActionStruct,
+    replay::{Replay, ReplayInput, ReplayPlayback},
+    save_game::{SAVE_GAME_VERSION, SaveGame},
+    scenario::Scenario,
+    status_effect::StatusEffect,
+    unit_definition::UnitDefinitions,
+    wave::{WaveDirector, WaveProgress, WaveScript},
 };
 
 const WATCH_POINTS_SIZE: usize = 10;
 
+/// Number of simulation ticks per second
+pub const TICKS_PER_SECOND: u64 = 60;
+
+/// Fixed duration of one simulation tick, the simulation never looks at the wall clock
+pub const TICK_DURATION: Duration = Duration::from_nanos(1_000_000_000 / TICKS_PER_SECOND);
+
 pub enum MainMessage {
     GetTerrain(TerrainTextureDetails), // Requests the terrain heightmap
+    GameCommand(GameCommand),          // Input of the player, only accepted in some phases
+    SaveGame,                          // Requests the whole simulation state
+    LoadGame(Box<SaveGame>),           // Replaces the simulation state
+    SaveReplay,                        // Requests the inputs recorded so far
 }
 
 pub enum WorkerMessage {
@@ -29,11 +53,23 @@ pub enum WorkerMessage {
     Ups(u32),
     TerrainData(Box<HeightMap>),
     Snapshot(Snapshot),
+    BattleEvents(Vec<BattleEvent>), // everything which happened during one tick
+    GameState(GameState),           // sent whenever the phase, gold or health changes
+    RoundFinished(RoundResult),
+    CommandRejected(String), // why the last game command could not be executed
+    GameSaved(Box<SaveGame>),
+    GameLoaded(GameState), // all ants are spawned again with the next snapshot
+    LoadFailed(String),
+    ReplaySaved(Box<Replay>),
+    ReplayFinished,             // live input is accepted again
+    WaveProgress(WaveProgress), // sent whenever a unit of the wave spawns or dies
 }
 
 // #[derive(Clone)]
 pub struct Snapshot {
+    pub time_stamp: Instant, // only used to interpolate the positions on the render side
     pub ant_actions: Vec<AntActionStruct>,
+    pub orb_actions: Vec<OrbActionStruct>,
 }
 
 // impl Snapshot {
@@ -79,58 +115,157 @@ pub struct Worker {
 
     // Debug
     ups: Fps,
+    last_update_time: Instant,
 
     watch_ups: Watch<WATCH_POINTS_SIZE>,
 
+    // Tick of the simulation minus the tick of the clock, changes when a game is loaded
+    tick_offset: i64,
+
+    // Replay
+    recording: Replay,
+    playback: Option<ReplayPlayback>,
+
     // Terrain
     terrain_generator: HeightMapGenerator,
+    navigation: NavigationGrid,
 
     // Game board
     game_board: GameBoard,
 
-    // Ant
-    ant_state: AntController,
-    ant_ai: AntAi,
+    // Game flow
+    game_flow: GameFlow,
+
+    // Ants
+    unit_definitions: UnitDefinitions,
+    ants: Vec<AntUnit>,
+
+    // Combat
+    combat: Combat,
+
+    // Waves, only in the PvE mode where the scenario has no enemies
+    waves: Option<WaveDirector>,
+    wave_progress: Option<WaveProgress>, // last one sent
 }
 
 impl Worker {
     pub fn new(
         channel_0_rx: mpsc::Receiver<MainMessage>,
         channel_1_tx: mpsc::Sender<WorkerMessage>,
+        scenario: Scenario,
+        unit_definitions: UnitDefinitions,
+        max_nr_ants: usize,
+        max_nr_orbs: usize,
     ) -> Self {
         // Debug
         let ups = Fps::new();
+        let last_update_time = Instant::now();
         let watch_ups = Watch::new();
 
+        let tick_offset = 0;
+
         // Terrain
         let terrain_generator = HeightMapGenerator::new();
+        let navigation = NavigationGrid::new();
+
+        // Waves
+        let is_pve = scenario
+            .units
+            .iter()
+            .all(|unit| unit.faction == Faction::Blue);
+        let waves = if is_pve {
+            match WaveScript::load_default(&unit_definitions) {
+                Ok(script) => Some(WaveDirector::new(script)),
+                Err(err) => {
+                    log::warn!("{}", err);
+                    None
+                }
+            }
+        } else {
+            None
+        };
+        let wave_progress = None;
+
+        // Replay
+        let recording = Replay::new(
+            scenario.clone(),
+            unit_definitions.clone(),
+            waves.as_ref().map(|waves| waves.script().clone()),
+            terrain_generator.seed(),
+            max_nr_ants,
+            max_nr_orbs,
+        );
+        let playback = None;
 
         // Game board
         let game_board = GameBoard::new();
 
-        // Ant
-        let ant_state = AntController::new(cgmath::Vector2 { x: 0.0, y: 0.0 }, 0);
-        let ant_ai = AntAi::new(Faction::Blue);
+        // Game flow
+        let game_flow = GameFlow::new(&scenario, Faction::Blue, max_nr_ants);
+        let _ = channel_1_tx.send(WorkerMessage::GameState(game_flow.state()));
+
+        // Ants
+        let ants = Self::create_ants(&game_flow, &unit_definitions);
+
+        // Combat
+        let combat = Combat::new(max_nr_orbs);
 
         Self {
             channel_0_rx,
             channel_1_tx,
 
             ups,
+            last_update_time,
             watch_ups,
 
+            tick_offset,
+
+            recording,
+            playback,
+
             terrain_generator,
+            navigation,
 
             game_board,
 
-            ant_state,
-            ant_ai,
+            game_flow,
+
+            unit_definitions,
+            ants,
+
+            combat,
+
+            waves,
+            wave_progress,
         }
     }
 
-    pub fn update(&mut self, _tick: u64, dt: Duration) {
-        let time_stamp = Instant::now();
+    /// Creates the worker the replay has been recorded with, it plays back the recorded inputs
+    pub fn from_replay(
+        channel_0_rx: mpsc::Receiver<MainMessage>,
+        channel_1_tx: mpsc::Sender<WorkerMessage>,
+        replay: Replay,
+    ) -> Self {
+        let mut worker = Self::new(
+            channel_0_rx,
+            channel_1_tx,
+            replay.scenario.clone(),
+            replay.unit_definitions.clone(),
+            replay.max_nr_ants,
+            replay.max_nr_orbs,
+        );
+
+        worker.terrain_generator = HeightMapGenerator::with_seed(replay.terrain_seed);
+        worker.navigation = NavigationGrid::new();
+        worker.recording.terrain_seed = replay.terrain_seed;
+        worker.recording.wave_script = replay.wave_script.clone();
+        worker.waves = replay.wave_script.clone().map(WaveDirector::new);
+        worker.playback = Some(ReplayPlayback::new(replay));
+
+        worker
+    }
 
+    pub fn update(&mut self, tick: u64) {
         let main = &self.channel_1_tx;
         let messages = &self.channel_0_rx;
 
@@ -141,11 +276,17 @@ impl Worker {
         let mut watch_index = 0;
 
         // update ups
-        self.ups.update(dt);
+        let now = Instant::now();
+        self.ups.update(now - self.last_update_time);
+        self.last_update_time = now;
         let _ = main.send(WorkerMessage::Ups(self.ups.get()));
 
         // Process messages
         let mut terrain_detail = Vec::new();
+        let mut game_commands = Vec::new();
+        let mut is_save_requested = false;
+        let mut save_games = Vec::new();
+        let mut is_replay_requested = false;
         self.watch_ups.start(watch_index, "Messages");
         {
             for message in messages.try_iter() {
@@ -154,8 +295,68 @@ impl Worker {
                     MainMessage::GetTerrain(terrain_texture_details) => {
                         terrain_detail.push(terrain_texture_details);
                     }
+                    // ##########################################################
+                    MainMessage::GameCommand(game_command) => {
+                        // only the recorded commands are executed during a playback
+                        if self.playback.is_none() {
+                            game_commands.push(game_command);
+                        }
+                    }
+                    // ##########################################################
+                    MainMessage::SaveGame => {
+                        is_save_requested = true;
+                    }
+                    // ##########################################################
+                    MainMessage::LoadGame(save_game) => {
+                        if self.playback.is_none() {
+                            save_games.push(save_game);
+                        }
+                    }
+                    // ##########################################################
+                    MainMessage::SaveReplay => {
+                        is_replay_requested = true;
+                    }
+                }
+            }
+        }
+        self.watch_ups.stop(watch_index);
+
+        // Replay
+        watch_index += 1;
+        self.watch_ups.start(watch_index, "Replay");
+        {
+            if let Some(playback) = &mut self.playback {
+                if playback.is_finished(tick) {
+                    self.playback = None;
+                    let _ = self.channel_1_tx.send(WorkerMessage::ReplayFinished);
+                } else {
+                    for input in playback.take_inputs(tick) {
+                        match input {
+                            ReplayInput::GameCommand(game_command) => {
+                                game_commands.push(game_command)
+                            }
+                            ReplayInput::LoadGame(save_game) => save_games.push(save_game),
+                        }
+                    }
                 }
             }
+
+            // loaded games come first, as they are executed first
+            for save_game in &save_games {
+                let input = ReplayInput::LoadGame(save_game.clone());
+                self.recording.record(tick, input);
+            }
+            for game_command in &game_commands {
+                let input = ReplayInput::GameCommand(game_command.clone());
+                self.recording.record(tick, input);
+            }
+
+            if is_replay_requested {
+                let replay = self.recording.until(tick);
+                let _ = self
+                    .channel_1_tx
+                    .send(WorkerMessage::ReplaySaved(Box::new(replay)));
+            }
         }
         self.watch_ups.stop(watch_index);
 
@@ -170,30 +371,535 @@ impl Worker {
         }
         self.watch_ups.stop(watch_index);
 
-        // Ant
+        // Save game
         watch_index += 1;
-        self.watch_ups.start(watch_index, "Update Ant");
+        self.watch_ups.start(watch_index, "Save Game");
         {
-            let ant_state = &mut self.ant_state;
+            for save_game in save_games {
+                let message = match self.load(*save_game, tick) {
+                    Ok(()) => WorkerMessage::GameLoaded(self.game_flow.state()),
+                    Err(err) => WorkerMessage::LoadFailed(err),
+                };
+                let _ = self.channel_1_tx.send(message);
+            }
+
+            if is_save_requested {
+                let save_game = self.save(tick);
+                let _ = self
+                    .channel_1_tx
+                    .send(WorkerMessage::GameSaved(Box::new(save_game)));
+            }
+        }
+        self.watch_ups.stop(watch_index);
+
+        // everything below runs on the tick of the simulation
+        let tick = self.simulation_tick(tick);
+
+        let mut events: Vec<BattleEvent> = Vec::new();
+
+        // Game flow
+        watch_index += 1;
+        self.watch_ups.start(watch_index, "Update Game Flow");
+        {
+            for game_command in game_commands {
+                let message = match self.execute_command(game_command, tick) {
+                    Ok(()) => WorkerMessage::GameState(self.game_flow.state()),
+                    Err(err) => WorkerMessage::CommandRejected(err),
+                };
+                let _ = self.channel_1_tx.send(message);
+            }
+
+            self.update_waves(tick);
+            self.update_game_flow(tick, &mut events);
+        }
+        self.watch_ups.stop(watch_index);
+
+        // Ants
+        watch_index += 1;
+        self.watch_ups.start(watch_index, "Update Ants");
+        {
+            let main = &self.channel_1_tx;
             let game_board = &mut self.game_board;
 
-            // update ant_state by ant_ai
-            self.ant_ai.update(ant_state, game_board);
+            // rebuild the game board from the current state of the ants
+            game_board.update(&self.ants);
+
+            // the ai only takes over during the battle
+            let is_battle = self.game_flow.phase() == GamePhase::Battle;
 
-            // update state
             let mut actions: Vec<AntActionStruct> = Vec::new();
-            self.ant_state.update(&time_stamp, &mut actions);
+            let mut casts: Vec<(Cast, Faction)> = Vec::new();
+            for ant in &mut self.ants {
+                // update ant controller by ant ai
+                if is_battle && ant.controller.is_alive() {
+                    let target_id = ant.ai.target_id();
+                    ant.ai.update(&mut ant.controller, game_board);
+
+                    if let Some(new_target_id) = ant.ai.target_id()
+                        && target_id != Some(new_target_id)
+                    {
+                        events.push(BattleEvent {
+                            tick,
+                            kind: BattleEventKind::TargetAcquired {
+                                unit_id: ant.id,
+                                target_id: new_target_id,
+                            },
+                        });
+                    }
+                }
+
+                // update state
+                let first_action = actions.len();
+                ant.controller.update(
+                    tick,
+                    &self.terrain_generator,
+                    &mut self.navigation,
+                    game_board,
+                    &mut actions,
+                );
+                for action in &actions[first_action..] {
+                    if let AntAction::Spawn(spawn) = action.action {
+                        events.push(BattleEvent {
+                            tick,
+                            kind: BattleEventKind::UnitSpawned {
+                                unit_id: ant.id,
+                                faction: ant.faction,
+                                position: spawn.pos.truncate(),
+                            },
+                        });
+                    }
+                }
 
-            // // send to main
-            // let mut ants = [AnimationPosition::zero(); 16];
-            // ants[0].animation = 0;
-            // ants[0].pos = ant_pos.pos;
-            // ants[0].look_at = ant_pos.look_at;
+                // spawn projectiles
+                if let Some(shot) = ant.controller.take_shot() {
+                    let target_position = shot.target_position;
+                    if self
+                        .combat
+                        .spawn(shot, ant.faction, &self.terrain_generator)
+                    {
+                        events.push(BattleEvent {
+                            tick,
+                            kind: BattleEventKind::ShotFired {
+                                unit_id: ant.id,
+                                target_position,
+                            },
+                        });
+                    }
+                }
+
+                if let Some(cast) = ant.controller.take_cast() {
+                    casts.push((cast, ant.faction));
+                }
+            }
+
+            // resolve abilities, a dash has already been done by the controller
+            let mut hits: Vec<Hit> = Vec::new();
+            for (cast, faction) in casts {
+                events.push(BattleEvent {
+                    tick,
+                    kind: BattleEventKind::AbilityCast {
+                        unit_id: cast.owner_id,
+                        ability: cast.kind.name(),
+                    },
+                });
+
+                let mut agents = Vec::new();
+                match &cast.kind {
+                    AbilityKind::Slam {
+                        radius,
+                        damage,
+                        effect,
+                    } => {
+                        game_board.world_get_agents_in_radius(cast.position, *radius, &mut agents);
+                        for agent in &agents {
+                            if agent.is_alive && agent.faction != faction {
+                                hits.push(Hit {
+                                    source_id: cast.owner_id,
+                                    target_id: agent.id,
+                                    damage: *damage,
+                                    effect: *effect,
+                                });
+                            }
+                        }
+                    }
+                    AbilityKind::HealPulse { radius, heal } => {
+                        game_board.world_get_agents_in_radius(cast.position, *radius, &mut agents);
+                        for agent in &agents {
+                            if agent.is_alive
+                                && agent.faction == faction
+                                && let Some(ant) =
+                                    self.ants.iter_mut().find(|ant| ant.id == agent.id)
+                            {
+                                ant.controller.heal(*heal, &mut actions);
+                            }
+                        }
+                    }
+                    AbilityKind::Dash { .. } => {}
+                    AbilityKind::Summon { unit_type, count } => {
+                        // the summoned units only take part in this battle
+                        for i in 0..*count {
+                            let Some(id) = (0..self.game_flow.max_nr_units())
+                                .find(|id| self.ants.iter().all(|ant| ant.id != *id))
+                            else {
+                                log::warn!("no free id for the summon of ant {}", cast.owner_id);
+                                break;
+                            };
+
+                            let angle = std::f32::consts::TAU * i as f32 / *count as f32;
+                            let position = cast.position
+                                + SUMMON_DISTANCE * cgmath::Vector2::new(angle.cos(), angle.sin());
+                            let position = self
+                                .navigation
+                                .nearest_walkable_position(&self.terrain_generator, position)
+                                .unwrap_or(position);
+                            let ant = AntUnit::new(
+                                id,
+                                unit_type,
+                                position,
+                                faction,
+                                &self.unit_definitions,
+                            )
+                            .expect("unit type is validated");
+                            self.ants.push(ant);
+                        }
+                    }
+                }
+            }
+
+            // resolve combat, burning ants are hurt by whoever set them on fire
+            for ant in &mut self.ants {
+                ant.controller
+                    .update_status_effects(&mut hits, &mut actions);
+            }
+            let mut orb_actions: Vec<OrbActionStruct> = Vec::new();
+            self.combat
+                .update(tick, game_board, &mut hits, &mut orb_actions);
+            for hit in hits {
+                let Some(target) = self.ants.iter_mut().find(|ant| ant.id == hit.target_id) else {
+                    continue;
+                };
+                // an explosion or a burn may still reach an ant killed earlier in the tick
+                if !target.controller.is_alive() {
+                    continue;
+                }
+                events.push(BattleEvent {
+                    tick,
+                    kind: BattleEventKind::Hit {
+                        source_id: hit.source_id,
+                        target_id: hit.target_id,
+                        damage: hit.damage,
+                    },
+                });
+
+                let damage = target.controller.apply_damage(hit.damage, &mut actions);
+                target.damage_received += damage;
+
+                if damage > 0.0 {
+                    target.last_attacker_id = Some(hit.source_id);
+                    events.push(BattleEvent {
+                        tick,
+                        kind: BattleEventKind::Damage {
+                            source_id: hit.source_id,
+                            target_id: hit.target_id,
+                            damage,
+                            health: target.controller.health(),
+                        },
+                    });
+                }
+                if let Some(effect) = hit.effect
+                    && target.controller.is_alive()
+                {
+                    target
+                        .controller
+                        .apply_status_effect(StatusEffect::new(&effect, hit.source_id));
+                    events.push(BattleEvent {
+                        tick,
+                        kind: BattleEventKind::StatusEffectApplied {
+                            source_id: hit.source_id,
+                            target_id: hit.target_id,
+                            kind: effect.kind,
+                        },
+                    });
+                }
+                if !target.controller.is_alive() {
+                    events.push(BattleEvent {
+                        tick,
+                        kind: BattleEventKind::Death {
+                            unit_id: hit.target_id,
+                            killer_id: hit.source_id,
+                        },
+                    });
+                }
+
+                if let Some(source) = self.ants.iter_mut().find(|ant| ant.id == hit.source_id) {
+                    source.damage_dealt += damage;
+                    source.controller.gain_mana(damage * MANA_PER_DAMAGE_DEALT);
+                }
+            }
 
             let _ = main.send(WorkerMessage::Snapshot(Snapshot {
+                time_stamp: Instant::now(),
                 ant_actions: actions,
+                orb_actions,
             }));
+            if !events.is_empty() {
+                let _ = main.send(WorkerMessage::BattleEvents(events));
+            }
         }
         self.watch_ups.stop(watch_index);
     }
+
+    /// The unit types have been validated when the scenario was created or the unit was bought
+    fn create_ants(game_flow: &GameFlow, unit_definitions: &UnitDefinitions) -> Vec<AntUnit> {
+        game_flow
+            .units()
+            .map(|unit| {
+                AntUnit::new(
+                    unit.id,
+                    &unit.unit_type,
+                    unit.position,
+                    unit.faction,
+                    unit_definitions,
+                )
+                .expect("unit type is validated")
+            })
+            .collect()
+    }
+
+    fn simulation_tick(&self, tick: u64) -> u64 {
+        tick.wrapping_add_signed(self.tick_offset)
+    }
+
+    /// Captures the state before the simulation tick belonging to the passed clock tick
+    pub fn save(&self, tick: u64) -> SaveGame {
+        SaveGame {
+            version: SAVE_GAME_VERSION,
+            tick: self.simulation_tick(tick),
+            terrain_seed: self.terrain_generator.seed(),
+            game_board: self.game_board.clone(),
+            game_flow: self.game_flow.clone(),
+            ants: self.ants.clone(),
+            combat: self.combat.clone(),
+            waves: self.waves.clone(),
+        }
+    }
+
+    /// The simulation continues with the saved tick at the passed clock tick
+    pub fn load(&mut self, save_game: SaveGame, tick: u64) -> Result<(), String> {
+        if save_game.version != SAVE_GAME_VERSION {
+            return Err(format!(
+                "save game: unsupported version {}, expected {}",
+                save_game.version, SAVE_GAME_VERSION
+            ));
+        }
+        if save_game.game_flow.max_nr_units() > self.game_flow.max_nr_units() {
+            return Err(format!(
+                "save game: up to {} units, only {} can be shown",
+                save_game.game_flow.max_nr_units(),
+                self.game_flow.max_nr_units()
+            ));
+        }
+        if save_game.combat.nr_orbs() != self.combat.nr_orbs() {
+            return Err(format!(
+                "save game: {} orbs, expected {}",
+                save_game.combat.nr_orbs(),
+                self.combat.nr_orbs()
+            ));
+        }
+        for ant in &save_game.ants {
+            self.unit_definitions.index_of(&ant.unit_type)?;
+        }
+
+        self.tick_offset = save_game.tick as i64 - tick as i64;
+        self.terrain_generator = HeightMapGenerator::with_seed(save_game.terrain_seed);
+        self.navigation = NavigationGrid::new();
+        self.game_board = save_game.game_board;
+        self.game_flow = save_game.game_flow;
+        self.ants = save_game.ants;
+        self.combat = save_game.combat;
+        self.waves = save_game.waves;
+        self.wave_progress = None;
+
+        Ok(())
+    }
+
+    pub fn start_battle(&mut self, tick: u64) -> Result<(), String> {
+        let tick = self.simulation_tick(tick);
+        self.game_flow.start_battle(tick)?;
+        self.start_wave(tick);
+
+        Ok(())
+    }
+
+    fn execute_command(&mut self, game_command: GameCommand, tick: u64) -> Result<(), String> {
+        match game_command {
+            GameCommand::BuyUnit { unit_type } => {
+                let unit = self.unit_definitions.get(&unit_type)?;
+                self.game_flow.buy_unit(unit)?;
+            }
+            GameCommand::PlaceUnit { id, slot } => {
+                self.game_flow.place_unit(id, slot)?;
+            }
+            GameCommand::SwapUnits { id, other_id } => {
+                self.game_flow.swap_units(id, other_id)?;
+            }
+            GameCommand::SetFormation(formation) => {
+                self.game_flow.set_formation(formation)?;
+            }
+            GameCommand::StartBattle => {
+                self.game_flow.start_battle(tick)?;
+                self.start_wave(tick);
+            }
+        }
+
+        // show the army as it will start the battle
+        if self.game_flow.phase() == GamePhase::Preparation {
+            self.ants = Self::create_ants(&self.game_flow, &self.unit_definitions);
+        }
+
+        Ok(())
+    }
+
+    /// Plans the wave of the current round, the units spawn during the battle
+    fn start_wave(&mut self, tick: u64) {
+        if let Some(waves) = &mut self.waves {
+            let round = self.game_flow.state().round;
+            waves.start(round, tick, &self.terrain_generator, &mut self.navigation);
+        }
+    }
+
+    /// Spawns the units of the wave which are due and reports the progress
+    fn update_waves(&mut self, tick: u64) {
+        let Some(waves) = &mut self.waves else {
+            return;
+        };
+        if self.game_flow.phase() != GamePhase::Battle {
+            return;
+        }
+
+        while let Some(spawn) = waves.next_spawn(tick) {
+            // the ids of the dead ants stay taken until the next round
+            let Some(id) = (0..self.game_flow.max_nr_units())
+                .find(|id| self.ants.iter().all(|ant| ant.id != *id))
+            else {
+                log::warn!("no free id for the {} of the wave", spawn.unit_type);
+                continue;
+            };
+
+            let mut ant = AntUnit::new(
+                id,
+                &spawn.unit_type,
+                spawn.position,
+                Faction::Red,
+                &self.unit_definitions,
+            )
+            .expect("unit type is validated");
+            ant.controller
+                .scale(waves.health_factor(), waves.damage_factor());
+            self.ants.push(ant);
+        }
+
+        let nr_alive = self
+            .ants
+            .iter()
+            .filter(|ant| ant.faction == Faction::Red && ant.controller.is_alive())
+            .count();
+        let progress = waves.progress(nr_alive);
+        if self.wave_progress != Some(progress) {
+            self.wave_progress = Some(progress);
+            let _ = self
+                .channel_1_tx
+                .send(WorkerMessage::WaveProgress(progress));
+        }
+    }
+
+    /// Moves on to the next phase once the current one is done
+    fn update_game_flow(&mut self, tick: u64, events: &mut Vec<BattleEvent>) {
+        let main = &self.channel_1_tx;
+
+        match self.game_flow.phase() {
+            // ##########################################################
+            GamePhase::Preparation => {
+                // wait for the player to start the battle
+            }
+            // ##########################################################
+            GamePhase::Battle => {
+                if self.is_battle_finished() || self.game_flow.is_battle_timed_out(tick) {
+                    let result = self.get_battle_result(self.game_flow.battle_duration(tick));
+                    let round_result = self.game_flow.finish_battle(&result, tick);
+                    events.push(BattleEvent {
+                        tick,
+                        kind: BattleEventKind::RoundEnded {
+                            round: round_result.round,
+                            winner: round_result.winner,
+                        },
+                    });
+
+                    let _ = main.send(WorkerMessage::RoundFinished(round_result));
+                    let _ = main.send(WorkerMessage::GameState(self.game_flow.state()));
+                }
+            }
+            // ##########################################################
+            GamePhase::Resolution => {
+                if self.game_flow.is_resolution_done(tick) {
+                    self.game_flow.start_next_round();
+                    if self.game_flow.phase() == GamePhase::Preparation {
+                        self.ants = Self::create_ants(&self.game_flow, &self.unit_definitions);
+                    }
+
+                    let _ = main.send(WorkerMessage::GameState(self.game_flow.state()));
+                }
+            }
+            // ##########################################################
+            GamePhase::GameOver => {}
+        }
+    }
+
+    /// The battle is finished as soon as at most one faction is left and no wave is spawning
+    pub fn is_battle_finished(&self) -> bool {
+        if self.waves.as_ref().is_some_and(WaveDirector::is_spawning) {
+            return false;
+        }
+
+        let mut alive = self.ants.iter().filter(|ant| ant.controller.is_alive());
+
+        match alive.next() {
+            Some(first) => alive.all(|ant| ant.faction == first.faction),
+            None => true,
+        }
+    }
+
+    pub fn get_battle_result(&self, tick: u64) -> BattleResult {
+        let is_finished = self.is_battle_finished();
+
+        let winner: Option<Faction> = if is_finished {
+            self.ants
+                .iter()
+                .find(|ant| ant.controller.is_alive())
+                .map(|ant| ant.faction)
+        } else {
+            None
+        };
+
+        let units = self
+            .ants
+            .iter()
+            .map(|ant| UnitResult {
+                id: ant.id,
+                unit_type: ant.unit_type.clone(),
+                faction: ant.faction,
+                is_alive: ant.controller.is_alive(),
+                health: ant.controller.health(),
+                damage_dealt: ant.damage_dealt,
+                damage_received: ant.damage_received,
+            })
+            .collect();
+
+        BattleResult {
+            is_finished,
+            winner,
+            ticks: tick,
+            units,
+        }
+    }
 }
@@ -12,7 +12,7 @@ pub struct InterpolatedPosition {
 }
 
 impl InterpolatedPosition {
-    pub fn zero() -> Self {
+    pub fn _zero() -> Self {
         let last_pos = cgmath::Vector3::zero();
         let pos = cgmath::Vector3::zero();
         let now = instant::Instant::now();
@@ -27,7 +27,7 @@ impl InterpolatedPosition {
         }
     }
 
-    pub fn _new(pos: cgmath::Vector3<f32>, time_stamp: instant::Instant) -> Self {
+    pub fn new(pos: cgmath::Vector3<f32>, time_stamp: instant::Instant) -> Self {
         let last_pos = pos;
         let last_time_stamp = time_stamp;
 
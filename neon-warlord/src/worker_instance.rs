@@ -1,23 +1,96 @@
 //! Instantiate a worker either single threaded or multi threaded
 
-use std::{sync::mpsc, thread, time::Duration};
-
-use crate::worker::{MainMessage, Worker, WorkerMessage};
+use std::{
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+        mpsc,
+    },
+    thread,
+    time::Duration,
+};
+
+use crate::{
+    replay::Replay,
+    scenario::Scenario,
+    unit_definition::UnitDefinitions,
+    worker::{MainMessage, TICK_DURATION, Worker, WorkerMessage},
+};
+
+/// Maximum number of ticks caught up within one frame of the single threaded worker
+const MAX_TICKS_PER_UPDATE: u32 = 4;
 
 pub struct WorkerInstance {
     instance: WorkerExecution,
 }
 
+/// How the worker is created once its channels exist
+enum WorkerSetup {
+    New {
+        scenario: Scenario,
+        unit_definitions: UnitDefinitions,
+        max_nr_ants: usize,
+        max_nr_orbs: usize,
+    },
+    Playback(Replay),
+}
+
+impl WorkerSetup {
+    fn create(
+        self,
+        channel_0_rx: mpsc::Receiver<MainMessage>,
+        channel_1_tx: mpsc::Sender<WorkerMessage>,
+    ) -> Worker {
+        match self {
+            WorkerSetup::New {
+                scenario,
+                unit_definitions,
+                max_nr_ants,
+                max_nr_orbs,
+            } => Worker::new(
+                channel_0_rx,
+                channel_1_tx,
+                scenario,
+                unit_definitions,
+                max_nr_ants,
+                max_nr_orbs,
+            ),
+            WorkerSetup::Playback(replay) => {
+                Worker::from_replay(channel_0_rx, channel_1_tx, replay)
+            }
+        }
+    }
+}
+
 impl WorkerInstance {
-    pub fn new() -> Self {
+    pub fn new(
+        scenario: Scenario,
+        unit_definitions: UnitDefinitions,
+        max_nr_ants: usize,
+        max_nr_orbs: usize,
+    ) -> Self {
+        Self::with_setup(WorkerSetup::New {
+            scenario,
+            unit_definitions,
+            max_nr_ants,
+            max_nr_orbs,
+        })
+    }
+
+    /// The worker plays back the recorded inputs and ignores the live ones until the replay ends
+    pub fn from_replay(replay: Replay) -> Self {
+        Self::with_setup(WorkerSetup::Playback(replay))
+    }
+
+    fn with_setup(setup: WorkerSetup) -> Self {
         let instance;
         #[cfg(target_arch = "wasm32")]
         {
-            instance = WorkerExecution::SingleThreaded(WorkerSingleThreaded::new());
+            instance = WorkerExecution::SingleThreaded(WorkerSingleThreaded::new(setup));
         }
         #[cfg(not(target_arch = "wasm32"))]
         {
-            instance = WorkerExecution::Multithreaded(WorkerMultiThreaded::new());
+            instance = WorkerExecution::Multithreaded(WorkerMultiThreaded::new(setup));
         }
 
         Self { instance }
@@ -62,30 +135,40 @@ pub struct WorkerSingleThreaded {
     worker: Box<Worker>,
 
     tick: u64,
+    accumulated_time: Duration,
 }
 
 #[allow(dead_code)] // unused in x86
 impl WorkerSingleThreaded {
-    pub fn new() -> Self {
+    fn new(setup: WorkerSetup) -> Self {
         let (channel_0_tx, channel_0_rx) = mpsc::channel();
         let (channel_1_tx, channel_1_rx) = mpsc::channel();
 
-        let worker = Box::new(Worker::new(channel_0_rx, channel_1_tx));
+        let worker = Box::new(setup.create(channel_0_rx, channel_1_tx));
 
         let tick = 0;
+        let accumulated_time = Duration::ZERO;
 
         Self {
             channel_0_tx,
             channel_1_rx,
             worker,
             tick,
+            accumulated_time,
         }
     }
 
+    /// Runs as many fixed ticks as fit into the passed time
     fn update(&mut self, dt: Duration) {
-        self.worker.update(self.tick, dt);
+        self.accumulated_time =
+            (self.accumulated_time + dt).min(TICK_DURATION * MAX_TICKS_PER_UPDATE);
+
+        while self.accumulated_time >= TICK_DURATION {
+            self.worker.update(self.tick);
 
-        self.tick += 1;
+            self.tick += 1;
+            self.accumulated_time -= TICK_DURATION;
+        }
     }
 }
 
@@ -93,33 +176,33 @@ pub struct WorkerMultiThreaded {
     channel_0_tx: mpsc::Sender<MainMessage>,
     channel_1_rx: mpsc::Receiver<WorkerMessage>,
 
+    is_running: Arc<AtomicBool>, // the thread stops when the instance is replaced
     _worker: thread::JoinHandle<()>,
 }
 
 #[allow(dead_code)] // unused in wasm
 impl WorkerMultiThreaded {
-    pub fn new() -> Self {
+    fn new(setup: WorkerSetup) -> Self {
         let (channel_0_tx, channel_0_rx) = mpsc::channel();
         let (channel_1_tx, channel_1_rx) = mpsc::channel();
 
+        let is_running = Arc::new(AtomicBool::new(true));
+        let is_worker_running = is_running.clone();
+
         let worker = thread::spawn(move || {
-            let mut worker = Worker::new(channel_0_rx, channel_1_tx);
+            let mut worker = setup.create(channel_0_rx, channel_1_tx);
 
             let mut tick = 0;
 
-            let mut last_update_time = instant::Instant::now();
-
-            loop {
+            while is_worker_running.load(Ordering::Relaxed) {
                 let start_time = instant::Instant::now();
-                let dt = start_time - last_update_time;
-                last_update_time = start_time;
-                worker.update(tick, dt);
+                worker.update(tick);
                 tick += 1;
                 let stop_time = instant::Instant::now();
 
                 let time_passed = stop_time - start_time;
 
-                let interval = Duration::from_millis(16);
+                let interval = TICK_DURATION;
                 if time_passed < interval {
                     thread::sleep(interval - time_passed);
                 }
@@ -129,7 +212,14 @@ impl WorkerMultiThreaded {
         Self {
             channel_0_tx,
             channel_1_rx,
+            is_running,
             _worker: worker,
         }
     }
 }
+
+impl Drop for WorkerMultiThreaded {
+    fn drop(&mut self) {
+        self.is_running.store(false, Ordering::Relaxed);
+    }
+}